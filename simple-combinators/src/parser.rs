@@ -29,9 +29,53 @@ pub fn space() -> impl Parser<ParseResult = char> {
     satisfy(|c: char| c.is_whitespace())
 }
 
-/// 解析任意数量空白符，返回()
+#[derive(Debug, Copy, Clone)]
+pub struct BlockComment;
+impl Parser for BlockComment {
+    type ParseResult = ();
+    fn parse<'b>(&self, buf: &mut &'b str) -> Result<Self::ParseResult, ParseError<'b>> {
+        let err = ParseError {
+            position: slice_some(buf),
+        };
+        if !buf.starts_with("/*") {
+            return Err(err);
+        }
+        let end = buf[2..].find("*/").ok_or(err)?; // 未闭合的块注释解析失败
+        *buf = &buf[end + 4..];
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LineComment;
+impl Parser for LineComment {
+    type ParseResult = ();
+    fn parse<'b>(&self, buf: &mut &'b str) -> Result<Self::ParseResult, ParseError<'b>> {
+        let err = ParseError {
+            position: slice_some(buf),
+        };
+        if !buf.starts_with('#') {
+            return Err(err);
+        }
+        let rest = &buf[1..];
+        if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+            return Err(err); // `#seed`、`#include`等不是注释
+        }
+        let end = rest.find('\n').unwrap_or(rest.len());
+        *buf = &rest[end..]; // 换行符留给空白符解析
+        Ok(())
+    }
+}
+
+/// 解析`/* */`括住的注释或`#`加空白开头、到行末为止的注释，返回()
+/// 不使用`//`作行注释，因为模板中的`//`表示两个换行符
+pub fn comment() -> impl Parser<ParseResult = ()> {
+    BlockComment.or(LineComment)
+}
+
+/// 解析任意数量空白符和注释，返回()
 pub fn spaces() -> impl Parser<ParseResult = ()> {
-    many(ignore(space()))
+    many(ignore(space()).or(comment()))
 }
 
 /// 解析所给字符串中的任意字符
@@ -51,11 +95,22 @@ where
     <I as FromStr>::Err: Error,
 {
     const EPS: f64 = 1e-10;
-    attempt(float().flat_map(|x| (x - x.trunc().abs() < EPS).then_some(I::from_f64(x)?))) // 小数部分足够小则解析成功
-        .or(attempt(
-            many1(one_of("-0123456789")).flat_map(|s: String| s.parse::<I>()), // 解析失败则尝试只解析数字和负号
-        ))
-        .or(many1(one_of("0123456789")).flat_map(|s: String| s.parse::<I>())) // 再解析失败则尝试只解析数字
+    attempt(
+        // 先尝试按整数精确解析，避免大整数经过浮点数时损失精度
+        many1(one_of("-0123456789"))
+            .and(optional(preview(one_of("Ee."))))
+            .flat_map(|(s, next): (String, Option<char>)| match next {
+                Some(_) => None,
+                None => s.parse::<I>().ok(),
+            }),
+    )
+    .or(attempt(float().flat_map(|x| {
+        (x - x.trunc().abs() < EPS).then_some(I::from_f64(x)?)
+    }))) // 小数部分足够小则解析成功
+    .or(attempt(
+        many1(one_of("-0123456789")).flat_map(|s: String| s.parse::<I>()), // 解析失败则尝试只解析数字和负号
+    ))
+    .or(many1(one_of("0123456789")).flat_map(|s: String| s.parse::<I>())) // 再解析失败则尝试只解析数字
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -66,7 +121,7 @@ impl<'a> Parser for Str<'a> {
     type ParseResult = &'a str;
     fn parse<'b>(&self, buf: &mut &'b str) -> Result<Self::ParseResult, ParseError<'b>> {
         let len = self.string.len();
-        if buf.len() >= len && &buf[..len] == self.string {
+        if buf.starts_with(self.string) {
             *buf = &buf[len..];
             Ok(self.string)
         } else {
@@ -36,6 +36,28 @@ mod tests {
 
         assert_ok!(spaces().parse(&mut "  \n  \t"), ());
 
+        let mut buf = " /* block\n comment */ /* another */ /";
+        assert_ok!(spaces().parse(&mut buf), ());
+        assert_eq!(buf, "/");
+        let mut buf = " //";
+        assert_ok!(spaces().parse(&mut buf), ());
+        assert_eq!(buf, "//");
+        let mut buf = "/* unclosed";
+        assert_ok!(spaces().parse(&mut buf), ());
+        assert_eq!(buf, "/* unclosed");
+        let mut buf = "# line comment\n  # another\n#seed[1]";
+        assert_ok!(spaces().parse(&mut buf), ());
+        assert_eq!(buf, "#seed[1]");
+        let mut buf = "1 # comment at the end";
+        assert_ok!(char('1').skip(spaces()).parse(&mut buf), '1');
+        assert_eq!(buf, "");
+        let mut buf = "#";
+        assert_ok!(spaces().parse(&mut buf), ());
+        assert_eq!(buf, "");
+        assert_err!(comment().parse(&mut "#include"));
+        assert_err!(comment().parse(&mut "//"));
+        assert_err!(comment().parse(&mut "/ /"));
+
         assert_ok!(one_of("abc").parse(&mut "cow"), 'c');
         let s = String::from("ocean");
         assert_ok!(one_of("aeiou").parse(&mut s.as_str()), 'o');
@@ -200,6 +222,11 @@ mod tests {
         assert_ok!(number::<i64>().parse(&mut "1.432e10"), 1.432e10 as i64);
         assert_ok!(number::<i64>().parse(&mut "1.432e2"), 1);
         assert_ok!(number::<i64>().parse(&mut "-1242.31"), -1242);
+        assert_ok!(
+            number::<i64>().parse(&mut "1000000000000000003"),
+            1000000000000000003
+        );
+        assert_ok!(number::<i64>().parse(&mut "-9223372036854775808"), i64::MIN);
 
         assert_ok!(number::<usize>().parse(&mut "1.432e10"), 1.432e10 as usize);
         assert_ok!(number::<usize>().parse(&mut "1.432e2"), 1);
@@ -0,0 +1,121 @@
+use crate::{details::error_info, random_range};
+
+/// 生成两个长度相同、不同的字符串，它们的多项式哈希值h = h * base + c在模modulus下相等
+/// modulus为None时表示对2^64自然溢出
+pub fn collision(base: i64, modulus: Option<i64>) -> (String, String) {
+    let d = match modulus {
+        None if base % 2 == 0 => {
+            // base^64在模2^64下为0，只需第一个字符不同
+            let mut d = vec![0; 65];
+            d[0] = 1;
+            d
+        }
+        None => thue_morse(11),
+        Some(m) if m < 2 => error_info(&format!(
+            "The modulus of the hash should be at least 2, but {} was given",
+            m
+        )),
+        Some(m) => tree_attack(base.rem_euclid(m) as u64, m as u64),
+    };
+    let (mut s, mut t) = (String::new(), String::new());
+    for x in d.into_iter() {
+        // 对应位置字符的差为d_i，差值之和正好是Σd_i·base^(n-1-i)
+        let c = if x == 0 {
+            random_range!(b'a', b'z')
+        } else {
+            random_range!(b'a', b'y')
+        };
+        let (a, b) = match x {
+            1 => (c + 1, c),
+            -1 => (c, c + 1),
+            _ => (c, c),
+        };
+        s.push(a as char);
+        t.push(b as char);
+    }
+    (s, t)
+}
+
+/// 长为2^k的Thue-Morse串与其反串的差，k>=11时对任意奇数base在模2^64下哈希值相同
+fn thue_morse(k: u32) -> Vec<i8> {
+    (0..1usize << k)
+        .map(|i| if i.count_ones() % 2 == 0 { 1 } else { -1 })
+        .collect()
+}
+
+/// 树攻击：寻找不全为0的d_i∈{-1,0,1}，使Σd_i·base^(n-1-i)≡0 (mod m)
+/// 每一轮把当前的值排序后两两相减，值会迅速变小直到出现0，失败时把长度加倍重试
+fn tree_attack(base: u64, m: u64) -> Vec<i8> {
+    for k in 1..=20 {
+        if let Some(d) = try_tree_attack(base, m, 1 << k) {
+            return d;
+        }
+    }
+    error_info(&format!(
+        "Failed to find a hash collision for base {} and modulus {}",
+        base, m
+    ))
+}
+
+fn try_tree_attack(base: u64, m: u64, n: usize) -> Option<Vec<i8>> {
+    let mut coef = vec![1i8; n];
+    let mut pow = 1u64;
+    let mut clusters: Vec<(u64, Vec<usize>)> = Vec::new();
+    for i in (0..n).rev() {
+        clusters.push((pow, vec![i]));
+        pow = (pow as u128 * base as u128 % m as u128) as u64;
+    }
+    loop {
+        if let Some((_, members)) = clusters.iter().find(|(v, _)| *v == 0) {
+            let mut d = vec![0; n];
+            for &i in members.iter() {
+                d[i] = coef[i];
+            }
+            return Some(d);
+        }
+        if clusters.len() < 2 {
+            return None;
+        }
+        clusters.sort_by_key(|(v, _)| *v);
+        let mut next = Vec::new();
+        let mut iter = clusters.into_iter();
+        while let (Some((v1, m1)), Some((v2, mut m2))) = (iter.next(), iter.next()) {
+            // 较小的一组取反，新的值为两者之差
+            for &i in m1.iter() {
+                coef[i] = -coef[i];
+            }
+            m2.extend(m1);
+            next.push((v2 - v1, m2));
+        }
+        clusters = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(s: &str, base: i64, modulus: Option<i64>) -> u128 {
+        let m = modulus.map_or(1u128 << 64, |m| m as u128);
+        let b = base.rem_euclid(m.min(i64::MAX as u128) as i64) as u128;
+        s.bytes().fold(0, |h, c| (h * b + c as u128) % m)
+    }
+
+    #[test]
+    fn test_collision() {
+        let cases = [
+            (131, Some(1_000_000_007)),
+            (-3, Some(998_244_353)),
+            (2, Some(2)),
+            (131, Some(1_000_000_000_000_000_003)),
+            (131, None),
+            (256, None),
+        ];
+        for &(base, modulus) in cases.iter() {
+            let (s, t) = collision(base, modulus);
+            assert_eq!(s.len(), t.len());
+            assert_ne!(s, t);
+            assert_eq!(hash(&s, base, modulus), hash(&t, base, modulus));
+        }
+    }
+}
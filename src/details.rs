@@ -39,13 +39,13 @@ macro_rules! resolve {
     ($t:expr, $ty:ident) => {
         match $t {
             Confirm(i) => i.clone(),
-            Lazy(g) => g.generate()?.$ty()?,
+            Lazy(g) => g.value()?.$ty()?,
         }
     };
     ($t:expr, $ty:ident, $T:ident) => {
         match $t {
             $T::Confirm(i) => i.clone(),
-            $T::Lazy(g) => g.generate()?.$ty()?,
+            $T::Lazy(g) => g.value()?.$ty()?,
         }
     };
 }
@@ -79,11 +79,13 @@ pub fn warning_info(info: &str) {
 }
 
 pub trait GetParameter {
-    fn get_str<'a>(&self, s: &'a str) -> Option<String>;
-    fn get_bool<'a>(&self, s: &'a str) -> Option<bool>;
+    fn get_str(&self, s: &str) -> Option<String>;
+    fn get_bool(&self, s: &str) -> Option<bool>;
+    fn get_int(&self, s: &str) -> Option<i64>;
+    fn get_enums(&self, s: &str) -> Option<Vec<String>>;
 }
 impl GetParameter for Config {
-    fn get_str<'a>(&self, s: &'a str) -> Option<String> {
+    fn get_str(&self, s: &str) -> Option<String> {
         use crate::token::StrParameter::*;
         let ps = self.get(s)?;
         match &ps[..] {
@@ -97,7 +99,7 @@ impl GetParameter for Config {
             }
         }
     }
-    fn get_bool<'a>(&self, s: &'a str) -> Option<bool> {
+    fn get_bool(&self, s: &str) -> Option<bool> {
         let ps = self.get(s)?;
         match &ps[..] {
             [Bool(val)] => Some(*val),
@@ -110,4 +112,35 @@ impl GetParameter for Config {
             }
         }
     }
+    fn get_int(&self, s: &str) -> Option<i64> {
+        use crate::token::IntParameter::*;
+        let ps = self.get(s)?;
+        match &ps[..] {
+            [Int(val)] => Some(resolve!(val, int)),
+            _ => {
+                warning_info(&format!(
+                    "The config '{}' has mismatched parameters (expected Int)",
+                    s
+                ));
+                None
+            }
+        }
+    }
+    fn get_enums(&self, s: &str) -> Option<Vec<String>> {
+        let ps = self.get(s)?;
+        let mut v = Vec::new();
+        for p in ps.iter() {
+            match p {
+                Enum(e) => v.push(e.clone()),
+                _ => {
+                    warning_info(&format!(
+                        "The config '{}' has mismatched parameters (expected Enum)",
+                        s
+                    ));
+                    return None;
+                }
+            }
+        }
+        Some(v)
+    }
 }
@@ -0,0 +1,268 @@
+use crate::{
+    details::{error_info, warning_info, Ignore},
+    subtask::{Subtask, Subtasks},
+};
+use std::{collections::HashSet, fs, path::Path};
+
+pub static FORMATS: &[&str] = &["luogu", "hustoj", "hydro", "domjudge", "polygon"];
+
+/// 一组生成好的输入输出文件（文件名相对于输出文件夹）
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub id: usize,
+    pub input: String,
+    pub output: Option<String>,
+}
+
+fn write(path: &Path, content: &str) {
+    fs::write(path, content).unwrap_or_else(|_| {
+        error_info(&format!(
+            "Some unknown error occurred while trying to write {}",
+            path.display()
+        ))
+    });
+}
+
+fn copy(folder: &Path, from: &str, to: &Path) {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).ignore();
+    }
+    fs::copy(folder.join(from), to).unwrap_or_else(|_| {
+        error_info(&format!(
+            "Some unknown error occurred while trying to copy {} to {}",
+            from,
+            to.display()
+        ))
+    });
+}
+
+/// 把total分平分给n个文件，不能整除时余数分给靠前的文件，返回第i个文件的分值
+fn share(total: i64, n: usize, i: usize) -> i64 {
+    let n = n as i64;
+    total / n + ((i as i64) < total % n) as i64
+}
+
+/// 每个文件的分值，没有子任务时平分100分，否则每个子任务的分数在属于它的文件间平分
+fn scores(cases: &[Case], subtasks: &Subtasks) -> Vec<i64> {
+    if subtasks.is_empty() {
+        return (0..cases.len())
+            .map(|i| share(100, cases.len(), i))
+            .collect();
+    }
+    // 同一编号只计一次，每个文件只属于第一个包含它的子任务
+    let mut seen = HashSet::new();
+    let owner: Vec<Option<&Subtask>> = cases
+        .iter()
+        .map(|c| match seen.insert(c.id) {
+            true => subtasks.find(c.id),
+            false => None,
+        })
+        .collect();
+    owner
+        .iter()
+        .enumerate()
+        .map(|(i, o)| match o {
+            Some(s) => {
+                let same: Vec<usize> = (0..owner.len())
+                    .filter(|&j| matches!(owner[j], Some(t) if t.name == s.name))
+                    .collect();
+                let k = same.iter().position(|&j| j == i).ignore();
+                share(s.score, same.len(), k)
+            }
+            None => 0,
+        })
+        .collect()
+}
+
+fn luogu(dir: &Path, folder: &Path, cases: &[Case], subtasks: &Subtasks) {
+    let mut config = String::new();
+    for (c, score) in cases.iter().zip(scores(cases, subtasks)) {
+        copy(folder, &c.input, &dir.join(&c.input));
+        if let Some(output) = &c.output {
+            copy(folder, output, &dir.join(output));
+        }
+        config.push_str(&format!("{}:\n  score: {}\n", c.input, score));
+        if let Some(s) = subtasks.find(c.id) {
+            config.push_str(&format!(
+                "  subtaskId: {}\n",
+                subtasks.position(&s.name).ignore()
+            ));
+        }
+    }
+    write(&dir.join("config.yml"), &config);
+}
+
+fn hustoj(dir: &Path, folder: &Path, cases: &[Case]) {
+    for c in cases.iter() {
+        copy(folder, &c.input, &dir.join(&c.input));
+        if let Some(output) = &c.output {
+            copy(folder, output, &dir.join(output));
+        }
+    }
+}
+
+fn hydro(dir: &Path, folder: &Path, cases: &[Case], subtasks: &Subtasks) {
+    let case_list = |ids: &Vec<usize>| {
+        let mut s = String::new();
+        for c in cases.iter().filter(|c| ids.contains(&c.id)) {
+            copy(folder, &c.input, &dir.join(&c.input));
+            s.push_str(&format!("      - input: {}\n", c.input));
+            if let Some(output) = &c.output {
+                copy(folder, output, &dir.join(output));
+                s.push_str(&format!("        output: {}\n", output));
+            }
+        }
+        s
+    };
+    let mut config = String::from("type: default\nsubtasks:\n");
+    if subtasks.is_empty() {
+        let ids = cases.iter().map(|c| c.id).collect();
+        config.push_str("  - id: 1\n    score: 100\n    cases:\n");
+        config.push_str(&case_list(&ids));
+    }
+    for (i, s) in subtasks.iter().enumerate() {
+        config.push_str(&format!(
+            "  - id: {}\n    score: {}\n    type: min\n",
+            i + 1,
+            s.score
+        ));
+        if !s.depends.is_empty() {
+            let depends: Vec<String> = s
+                .depends
+                .iter()
+                .map(|d| (subtasks.position(d).ignore() + 1).to_string())
+                .collect();
+            config.push_str(&format!("    if: [{}]\n", depends.join(", ")));
+        }
+        config.push_str("    cases:\n");
+        config.push_str(&case_list(&s.cases));
+    }
+    write(&dir.join("config.yml"), &config);
+}
+
+/// DOMjudge和Polygon按编号命名，编号补零到相同的宽度
+fn numbered(dir: &Path, folder: &Path, cases: &[Case], input: &str, output: &str) {
+    let width = cases
+        .iter()
+        .map(|c| c.id)
+        .max()
+        .unwrap_or(0)
+        .to_string()
+        .len()
+        .max(2);
+    for c in cases.iter() {
+        let name = format!("{:0width$}", c.id, width = width);
+        copy(folder, &c.input, &dir.join(input.replace("{}", &name)));
+        if let Some(o) = &c.output {
+            copy(folder, o, &dir.join(output.replace("{}", &name)));
+        }
+    }
+}
+
+fn zip(dir: &Path, target: &Path) {
+    let script = &format!(
+        "Compress-Archive -Path {} -DestinationPath {} -Force",
+        dir.join("*").to_str().ignore(),
+        target.to_str().ignore(),
+    );
+    powershell_script::run(script, false).unwrap_or_else(|_| {
+        error_info(&format!(
+            "Something went wrong while creating {}\nPlease check the powershell script: {}",
+            target.display(),
+            script
+        ))
+    });
+}
+
+pub fn export(format: &str, folder: &Path, cases: &[Case], subtasks: &Subtasks) {
+    println!("Exporting {}.zip", format);
+    if cases.iter().any(|c| c.output.is_none()) {
+        warning_info(&format!(
+            "Some files have no output (use #std to generate them), the {} package will be incomplete",
+            format
+        ));
+    }
+    let dir = folder.join(format);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap_or_else(|_| {
+            error_info(&format!(
+                "Failed to clean up the previous export in {}",
+                dir.display()
+            ))
+        });
+    }
+    fs::create_dir_all(&dir)
+        .unwrap_or_else(|_| error_info(&format!("Failed to create the folder {}", dir.display())));
+    match format {
+        "luogu" => luogu(&dir, folder, cases, subtasks),
+        "hustoj" => hustoj(&dir, folder, cases),
+        "hydro" => hydro(&dir, folder, cases, subtasks),
+        "domjudge" => numbered(
+            &dir,
+            folder,
+            cases,
+            "data/secret/{}.in",
+            "data/secret/{}.ans",
+        ),
+        "polygon" => numbered(&dir, folder, cases, "tests/{}", "tests/{}.a"),
+        _ => unreachable!(),
+    }
+    zip(&dir, &folder.join(format!("{}.zip", format)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtask::Mark;
+
+    fn cases(ids: &[usize]) -> Vec<Case> {
+        ids.iter()
+            .map(|&id| Case {
+                id,
+                input: format!("{}.in", id),
+                output: None,
+            })
+            .collect()
+    }
+
+    fn mark(name: &str, score: i64) -> Mark {
+        Mark {
+            name: name.to_string(),
+            score: Some(score),
+            depends: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_scores_without_subtasks() {
+        assert!(scores(&[], &Subtasks::default()).is_empty());
+        assert_eq!(
+            scores(&cases(&[1, 2, 3]), &Subtasks::default()),
+            [34, 33, 33]
+        );
+        let s = scores(&cases(&(1..=7).collect::<Vec<_>>()), &Subtasks::default());
+        assert_eq!(s.iter().sum::<i64>(), 100);
+    }
+
+    #[test]
+    fn test_scores_with_subtasks() {
+        let mut subtasks = Subtasks::default();
+        subtasks.add(mark("small", 30), 1..3);
+        subtasks.add(mark("large", 70), 3..6);
+        assert_eq!(
+            scores(&cases(&[1, 2, 3, 4, 5]), &subtasks),
+            [15, 15, 24, 23, 23]
+        );
+    }
+
+    #[test]
+    fn test_scores_duplicate_ids() {
+        let mut subtasks = Subtasks::default();
+        subtasks.add(mark("all", 70), 1..4);
+        subtasks.add(mark("all", 70), 2..4);
+        let s = scores(&cases(&[1, 2, 3]), &subtasks);
+        assert_eq!(s, [24, 23, 23]);
+        // 未属于任何子任务的文件不计分
+        assert_eq!(scores(&cases(&[1, 4]), &subtasks), [70, 0]);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    details::warning_info,
+    token::{Config, Parameter::*, StrParameter},
+};
+/// 对生成结果的格式要求，由`#format`配置
+#[derive(Clone, Debug)]
+pub struct Format {
+    pub sep: String,
+    pub trailing: bool,
+    pub final_newline: Option<bool>, // None表示保持原样
+    pub crlf: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Format {
+            sep: String::from(" "),
+            trailing: true,
+            final_newline: None,
+            crlf: false,
+        }
+    }
+}
+
+impl Format {
+    pub fn from_config(config: &Config) -> Format {
+        let mut format = Format::default();
+        for p in config.get("format").into_iter().flatten() {
+            match p {
+                Named(k, v) => match (k.as_str(), &**v) {
+                    ("sep", Str(StrParameter::Confirm(s))) => format.sep = s.clone(),
+                    ("trailing", Bool(b)) => format.trailing = *b,
+                    ("newline", Bool(b)) => format.final_newline = Some(*b),
+                    ("eol", Enum(e)) if e == "lf" => format.crlf = false,
+                    ("eol", Enum(e)) if e == "crlf" => format.crlf = true,
+                    _ => warning_info(&format!("unsupported format option: {}", k)),
+                },
+                _ => warning_info("The config 'format' only accepts named parameters"),
+            }
+        }
+        format
+    }
+    /// 统一行末空白、换行符和文件末尾的换行
+    pub fn normalize(&self, s: String) -> String {
+        if self.trailing && self.final_newline.is_none() && !self.crlf {
+            return s; // 默认格式下不做任何处理
+        }
+        let eol = if self.crlf { "\r\n" } else { "\n" };
+        let lines: Vec<&str> = s
+            .split('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l))
+            .map(|l| if self.trailing { l } else { self.trim(l) })
+            .collect();
+        let mut s = lines.join(eol);
+        match self.final_newline {
+            Some(true) if !s.ends_with(eol) => s.push_str(eol),
+            Some(false) => s.truncate(s.trim_end_matches(['\r', '\n']).len()),
+            _ => {}
+        }
+        s
+    }
+    /// 删除行末的分隔符、空格和制表符
+    fn trim<'a>(&self, mut line: &'a str) -> &'a str {
+        loop {
+            let mut t = line.trim_end_matches([' ', '\t']);
+            if !self.sep.is_empty() {
+                t = t.trim_end_matches(self.sep.as_str());
+            }
+            if t.len() == line.len() {
+                return line;
+            }
+            line = t;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trailing_separator() {
+        let format = Format {
+            sep: String::from(","),
+            trailing: false,
+            ..Format::default()
+        };
+        assert_eq!(
+            format.normalize(String::from("1,2,3,\n4, \t\n")),
+            "1,2,3\n4\n"
+        );
+        let format = Format {
+            trailing: false,
+            ..Format::default()
+        };
+        assert_eq!(format.normalize(String::from("1 2 \n3 \n")), "1 2\n3\n");
+    }
+
+    #[test]
+    fn test_newline_and_eol() {
+        let format = Format {
+            final_newline: Some(true),
+            crlf: true,
+            ..Format::default()
+        };
+        assert_eq!(format.normalize(String::from("1 \n2 ")), "1 \r\n2 \r\n");
+        let format = Format {
+            final_newline: Some(false),
+            ..Format::default()
+        };
+        assert_eq!(format.normalize(String::from("1\r\n2\n\n")), "1\n2");
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    details::{error_info, Ignore},
+    random::with_rng,
+    random_range,
+    token::GeoKind,
+};
+use num::integer::gcd;
+use rand::seq::SliceRandom;
+use std::collections::HashSet;
+
+type Point = (i64, i64);
+
+/// 按kind生成坐标在[l, r]内的若干行，每行为一个点或一条线段的两个端点
+pub fn geometry(kind: GeoKind, n: usize, l: i64, r: i64, clockwise: bool) -> Vec<Vec<Point>> {
+    if l > r || r as i128 - l as i128 > i64::MAX as i128 {
+        error_info(&format!(
+            "Tried to generate coordinates between {} and {}",
+            l, r
+        ));
+    }
+    let rows = |v: Vec<Point>| v.into_iter().map(|p| vec![p]).collect();
+    match kind {
+        GeoKind::Points => rows((0..n).map(|_| point(l, r)).collect()),
+        GeoKind::Distinct => rows(distinct(n, l, r)),
+        GeoKind::General => rows(general(n, l, r)),
+        GeoKind::Convex => rows(orient(convex(n, l, r), clockwise)),
+        GeoKind::Polygon => rows(orient(polygon(n, l, r), clockwise)),
+        GeoKind::Segments => (0..n).map(|_| segment(l, r)).collect(),
+    }
+}
+
+/// 把放大了10^precision倍的整数坐标输出为precision位小数
+pub fn format_coord(x: i64, precision: u32) -> String {
+    if precision == 0 {
+        return x.to_string();
+    }
+    let scale = 10i128.pow(precision);
+    let sign = if x < 0 { "-" } else { "" };
+    let abs = (x as i128).abs();
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        abs / scale,
+        abs % scale,
+        width = precision as usize
+    )
+}
+
+fn point(l: i64, r: i64) -> Point {
+    (random_range!(l, r), random_range!(l, r))
+}
+
+fn cross(a: Point, b: Point) -> i128 {
+    a.0 as i128 * b.1 as i128 - a.1 as i128 * b.0 as i128
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn check_count(n: usize, count: i128, what: &str, l: i64, r: i64) {
+    if n as i128 > count {
+        error_info(&format!(
+            "Tried to generate {} {} with coordinates between {} and {}",
+            n, what, l, r
+        ));
+    }
+}
+
+fn distinct(n: usize, l: i64, r: i64) -> Vec<Point> {
+    let side = r as i128 - l as i128 + 1;
+    check_count(n, side * side, "distinct points", l, r);
+    let mut set = HashSet::new();
+    let mut v = Vec::new();
+    while v.len() < n {
+        let p = point(l, r);
+        if set.insert(p) {
+            v.push(p);
+        }
+    }
+    v
+}
+
+/// 不断随机生成候选点，与已有的点都不重合、且不与其中任意两点共线时才加入
+/// 候选点到已有各点的方向约分后两两不同，即说明没有三点共线
+fn general(n: usize, l: i64, r: i64) -> Vec<Point> {
+    let side = r as i128 - l as i128 + 1;
+    check_count(n, side * side, "distinct points", l, r);
+    let mut v: Vec<Point> = Vec::new();
+    let mut attempts = 0;
+    while v.len() < n {
+        attempts += 1;
+        if attempts > 100 * n + 1000 {
+            error_info(&format!(
+                "Failed to generate {} points with no three collinear between {} and {}, try a larger range",
+                n, l, r
+            ));
+        }
+        let p = point(l, r);
+        let mut directions = HashSet::new();
+        let valid = v.iter().all(|&q| {
+            let (dx, dy) = sub(q, p);
+            if dx == 0 && dy == 0 {
+                return false;
+            }
+            let g = gcd(dx, dy);
+            let (dx, dy) = (dx / g, dy / g);
+            let d = if dx < 0 || dx == 0 && dy < 0 {
+                (-dx, -dy)
+            } else {
+                (dx, dy)
+            };
+            directions.insert(d)
+        });
+        if valid {
+            v.push(p);
+        }
+    }
+    v
+}
+
+/// n个互不相同的数，按Valtr算法随机分到两条链上，得到和为0、都不为0的n个分量
+fn components(n: usize, l: i64, r: i64) -> Vec<i64> {
+    let mut set = HashSet::new();
+    while set.len() < n {
+        set.insert(random_range!(l, r));
+    }
+    let mut v: Vec<i64> = set.into_iter().collect();
+    v.sort();
+    let (mut last1, mut last2) = (v[0], v[0]);
+    let mut res = Vec::new();
+    for &x in v[1..n - 1].iter() {
+        if random_range!(0, 1) == 0 {
+            res.push(x - last1);
+            last1 = x;
+        } else {
+            res.push(last2 - x);
+            last2 = x;
+        }
+    }
+    res.push(v[n - 1] - last1);
+    res.push(last2 - v[n - 1]);
+    res
+}
+
+/// 向量是否在下半平面（含负x轴），按极角排序时上半平面的向量在前
+fn lower_half(p: Point) -> bool {
+    p.1 < 0 || p.1 == 0 && p.0 < 0
+}
+
+/// Valtr算法：x、y方向的分量随机配对成n条边，按极角排序后依次相连得到逆时针的凸多边形
+/// 有两条边方向相同时会出现三点共线，此时重新生成
+fn convex(n: usize, l: i64, r: i64) -> Vec<Point> {
+    if n < 3 {
+        error_info(&format!(
+            "Tried to generate a convex polygon with {} vertices",
+            n
+        ));
+    }
+    check_count(
+        n,
+        r as i128 - l as i128 + 1,
+        "vertices of a convex polygon",
+        l,
+        r,
+    );
+    for _ in 0..1000 {
+        let xs = components(n, l, r);
+        let mut ys = components(n, l, r);
+        with_rng(|rng| ys.shuffle(rng));
+        let mut edges: Vec<Point> = xs.into_iter().zip(ys).collect();
+        edges.sort_by(|&a, &b| {
+            lower_half(a)
+                .cmp(&lower_half(b))
+                .then_with(|| 0.cmp(&cross(a, b)))
+        });
+        let parallel = edges
+            .windows(2)
+            .any(|w| lower_half(w[0]) == lower_half(w[1]) && cross(w[0], w[1]) == 0);
+        if parallel {
+            continue;
+        }
+        let mut v = vec![(0, 0)];
+        for e in edges[..n - 1].iter() {
+            let last = v[v.len() - 1];
+            v.push((last.0 + e.0, last.1 + e.1));
+        }
+        // x、y方向的跨度恰好是最大与最小分量之差，不超过r - l，可以随机平移到范围内
+        let (min_x, max_x) = (
+            v.iter().map(|p| p.0).min().ignore(),
+            v.iter().map(|p| p.0).max().ignore(),
+        );
+        let (min_y, max_y) = (
+            v.iter().map(|p| p.1).min().ignore(),
+            v.iter().map(|p| p.1).max().ignore(),
+        );
+        let dx = l - min_x + random_range!(0, (r - l) - (max_x - min_x));
+        let dy = l - min_y + random_range!(0, (r - l) - (max_y - min_y));
+        return v.into_iter().map(|(x, y)| (x + dx, y + dy)).collect();
+    }
+    error_info(&format!(
+        "Failed to generate a convex polygon with {} vertices between {} and {}, try a larger range",
+        n, l, r
+    ))
+}
+
+/// 取没有三点共线的点，以最低（其次最左）的点为中心按极角排序，依次相连得到逆时针的简单多边形
+fn polygon(n: usize, l: i64, r: i64) -> Vec<Point> {
+    if n < 3 {
+        error_info(&format!("Tried to generate a polygon with {} vertices", n));
+    }
+    let mut v = general(n, l, r);
+    let (i, _) = v
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| (p.1, p.0))
+        .ignore();
+    let center = v.swap_remove(i);
+    v.sort_by(|&a, &b| 0.cmp(&cross(sub(a, center), sub(b, center))));
+    v.insert(0, center);
+    v
+}
+
+/// 随机选择起点，并按需要改为顺时针
+fn orient(mut v: Vec<Point>, clockwise: bool) -> Vec<Point> {
+    if clockwise {
+        v.reverse();
+    }
+    let k = random_range!(0, v.len() - 1);
+    v.rotate_left(k);
+    v
+}
+
+fn segment(l: i64, r: i64) -> Vec<Point> {
+    if l == r {
+        error_info(&format!(
+            "Tried to generate a segment with coordinates between {} and {}",
+            l, r
+        ));
+    }
+    let p = point(l, r);
+    loop {
+        let q = point(l, r);
+        if q != p {
+            return vec![p, q];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(kind: GeoKind, n: usize, l: i64, r: i64, clockwise: bool) -> Vec<Point> {
+        let v: Vec<Point> = geometry(kind, n, l, r, clockwise)
+            .into_iter()
+            .map(|row| row[0])
+            .collect();
+        assert_eq!(v.len(), n);
+        assert!(v
+            .iter()
+            .all(|p| l <= p.0 && p.0 <= r && l <= p.1 && p.1 <= r));
+        v
+    }
+
+    fn sign(x: i128) -> i128 {
+        x.signum()
+    }
+
+    /// 线段ab与cd是否有公共点
+    fn intersect(a: Point, b: Point, c: Point, d: Point) -> bool {
+        let on = |p: Point, q: Point, x: Point| {
+            cross(sub(q, p), sub(x, p)) == 0
+                && p.0.min(q.0) <= x.0
+                && x.0 <= p.0.max(q.0)
+                && p.1.min(q.1) <= x.1
+                && x.1 <= p.1.max(q.1)
+        };
+        let d1 = sign(cross(sub(b, a), sub(c, a)));
+        let d2 = sign(cross(sub(b, a), sub(d, a)));
+        let d3 = sign(cross(sub(d, c), sub(a, c)));
+        let d4 = sign(cross(sub(d, c), sub(b, c)));
+        d1 * d2 < 0 && d3 * d4 < 0 || on(a, b, c) || on(a, b, d) || on(c, d, a) || on(c, d, b)
+    }
+
+    /// 不相邻的边没有公共点，相邻的边只在公共顶点处相交
+    fn is_simple(v: &[Point]) -> bool {
+        let n = v.len();
+        let edge = |i: usize| (v[i], v[(i + 1) % n]);
+        for i in 0..n {
+            for j in i + 1..n {
+                let ((a, b), (c, d)) = (edge(i), edge(j));
+                let adjacent = j == i + 1 || i == 0 && j == n - 1;
+                if adjacent {
+                    // 相邻的两条边从公共顶点o出发，方向相同时会重叠
+                    let (o, p, q) = if j == i + 1 { (b, a, d) } else { (a, b, c) };
+                    let (u, w) = (sub(p, o), sub(q, o));
+                    if cross(u, w) == 0 && u.0 as i128 * w.0 as i128 + u.1 as i128 * w.1 as i128 > 0
+                    {
+                        return false;
+                    }
+                } else if intersect(a, b, c, d) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// 有向面积的两倍，逆时针为正
+    fn area(v: &[Point]) -> i128 {
+        (0..v.len())
+            .map(|i| cross(v[i], v[(i + 1) % v.len()]))
+            .sum()
+    }
+
+    fn no_three_collinear(v: &[Point]) -> bool {
+        let n = v.len();
+        (0..n).all(|i| {
+            (i + 1..n).all(|j| (j + 1..n).all(|k| cross(sub(v[j], v[i]), sub(v[k], v[i])) != 0))
+        })
+    }
+
+    #[test]
+    fn test_convex() {
+        for &(n, l, r) in [(3, 0, 2), (4, 0, 3), (10, -100, 100), (30, 0, 1000)].iter() {
+            for &clockwise in [false, true].iter() {
+                for _ in 0..20 {
+                    let v = points(GeoKind::Convex, n, l, r, clockwise);
+                    let turn = if clockwise { -1 } else { 1 };
+                    assert!(is_simple(&v), "{:?}", v);
+                    assert_eq!(sign(area(&v)), turn);
+                    assert!((0..n).all(|i| {
+                        let (a, b, c) = (v[i], v[(i + 1) % n], v[(i + 2) % n]);
+                        sign(cross(sub(b, a), sub(c, b))) == turn
+                    }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_polygon() {
+        for &(n, l, r) in [(3, 0, 2), (5, 0, 3), (20, -50, 50), (50, 0, 1000)].iter() {
+            for &clockwise in [false, true].iter() {
+                for _ in 0..20 {
+                    let v = points(GeoKind::Polygon, n, l, r, clockwise);
+                    assert!(is_simple(&v), "{:?}", v);
+                    assert!(no_three_collinear(&v));
+                    assert_eq!(sign(area(&v)), if clockwise { -1 } else { 1 });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_points() {
+        let v = points(GeoKind::Distinct, 16, 0, 3, false);
+        assert_eq!(v.iter().collect::<HashSet<_>>().len(), 16);
+        let v = points(GeoKind::General, 12, 0, 20, false);
+        assert!(no_three_collinear(&v));
+        for row in geometry(GeoKind::Segments, 50, 0, 1, false) {
+            assert_eq!(row.len(), 2);
+            assert_ne!(row[0], row[1]);
+        }
+    }
+}
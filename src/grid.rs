@@ -0,0 +1,369 @@
+use crate::{
+    details::error_info,
+    random::with_rng,
+    random_range,
+    token::{MazeAlgo, MazeOption},
+};
+use rand::seq::SliceRandom;
+use std::collections::{HashSet, VecDeque};
+
+/// 生成n行m列的字符网格，chars的第一个字符为空地，其余为障碍
+/// density为障碍所占的百分比，为None时每个格子在chars中均匀选择
+/// path为路径起点和终点的行列（从1开始），指定时保证两者之间有一条只经过空地的路径
+pub fn grid(
+    n: usize,
+    m: usize,
+    chars: &[char],
+    density: Option<i64>,
+    path: Option<[(usize, usize); 2]>,
+) -> Vec<Vec<char>> {
+    if let Some(d) = density {
+        if !(0..=100).contains(&d) {
+            error_info(&format!(
+                "The density of a grid should be between 0 and 100, but {} was given",
+                d
+            ));
+        }
+    }
+    let mut g: Vec<Vec<char>> = (0..n)
+        .map(|_| (0..m).map(|_| cell(chars, density)).collect())
+        .collect();
+    if let Some([from, to]) = path.filter(|_| n > 0 && m > 0) {
+        for &(x, y) in [from, to].iter() {
+            if x < 1 || x > n || y < 1 || y > m {
+                error_info(&format!(
+                    "The endpoint ({}, {}) of the path is outside the {} x {} grid",
+                    x, y, n, m
+                ));
+            }
+        }
+        carve(
+            &mut g,
+            chars[0],
+            (from.0 - 1, from.1 - 1),
+            (to.0 - 1, to.1 - 1),
+        );
+    }
+    g
+}
+
+fn cell(chars: &[char], density: Option<i64>) -> char {
+    match density {
+        Some(d) if random_range!(1, 100) > d => chars[0],
+        Some(_) => chars[random_range!(1, chars.len() - 1)],
+        None => chars[random_range!(0, chars.len() - 1)],
+    }
+}
+
+/// 用0-1 BFS找一条从from到to、经过障碍最少的路径，再把路径上的障碍改为空地
+/// 每次扩展时方向的顺序随机，使路径的形状也是随机的
+fn carve(g: &mut [Vec<char>], free: char, from: (usize, usize), to: (usize, usize)) {
+    let (n, m) = (g.len(), g[0].len());
+    let mut dist = vec![vec![usize::MAX; m]; n];
+    let mut prev = vec![vec![None; m]; n];
+    let mut queue = VecDeque::new();
+    dist[from.0][from.1] = 0;
+    queue.push_back(from);
+    while let Some((x, y)) = queue.pop_front() {
+        let mut dirs = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+        with_rng(|rng| dirs.shuffle(rng));
+        for (dx, dy) in dirs.iter() {
+            let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+            if nx < 0 || ny < 0 || nx >= n as i64 || ny >= m as i64 {
+                continue;
+            }
+            let (nx, ny) = (nx as usize, ny as usize);
+            let w = (g[nx][ny] != free) as usize;
+            if dist[x][y] + w < dist[nx][ny] {
+                dist[nx][ny] = dist[x][y] + w;
+                prev[nx][ny] = Some((x, y));
+                if w == 0 {
+                    queue.push_front((nx, ny));
+                } else {
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+    let (mut x, mut y) = to;
+    loop {
+        g[x][y] = free;
+        match prev[x][y] {
+            Some((px, py)) => {
+                x = px;
+                y = py;
+            }
+            None => break,
+        }
+    }
+}
+
+/// 生成n行m列的迷宫，行列坐标都是偶数的格子为房间，房间之间的格子为墙或通道
+/// 先按algo生成房间的一棵随机生成树（完美迷宫），再额外打通extra面墙使迷宫中出现环
+pub fn maze(n: usize, m: usize, option: &MazeOption, extra: usize) -> Vec<Vec<char>> {
+    if n == 0 || m == 0 {
+        error_info(&format!("Tried to generate a maze of size {} x {}", n, m));
+    }
+    let (r, c) = (n - n / 2, m - m / 2);
+    let mut adj = vec![Vec::new(); r * c];
+    let mut walls = Vec::new();
+    for x in 0..r {
+        for y in 0..c {
+            let u = x * c + y;
+            if x + 1 < r {
+                walls.push((u, u + c));
+            }
+            if y + 1 < c {
+                walls.push((u, u + 1));
+            }
+        }
+    }
+    for &(u, v) in walls.iter() {
+        adj[u].push(v);
+        adj[v].push(u);
+    }
+    let opened: HashSet<(usize, usize)> = match option.algo {
+        MazeAlgo::Dfs => dfs(&adj),
+        MazeAlgo::Prim => prim(&adj),
+        MazeAlgo::Kruskal => kruskal(r * c, walls.clone()),
+    }
+    .into_iter()
+    .map(|(u, v)| (u.min(v), u.max(v)))
+    .collect();
+    let closed: Vec<(usize, usize)> = walls.into_iter().filter(|w| !opened.contains(w)).collect();
+    if extra > closed.len() {
+        error_info(&format!(
+            "Tried to open {} extra walls in a maze, but only {} walls can be opened",
+            extra,
+            closed.len()
+        ));
+    }
+    let braided: Vec<(usize, usize)> =
+        with_rng(|rng| closed.choose_multiple(rng, extra).cloned().collect());
+    let mut g = vec![vec![option.wall; m]; n];
+    for x in 0..r {
+        for y in 0..c {
+            g[2 * x][2 * y] = option.path;
+        }
+    }
+    for &(u, v) in opened.iter().chain(braided.iter()) {
+        // 两个房间之间的格子坐标为两个房间坐标之和
+        g[u / c + v / c][u % c + v % c] = option.path;
+    }
+    // n或m为偶数时，最后一行或一列的格子只与相邻的房间相连，不会产生环
+    if n & 1 == 0 {
+        for y in (0..m).step_by(2) {
+            if random_range!(0, 1) == 1 {
+                g[n - 1][y] = option.path;
+            }
+        }
+    }
+    if m & 1 == 0 {
+        for x in (0..n).step_by(2) {
+            if random_range!(0, 1) == 1 {
+                g[x][m - 1] = option.path;
+            }
+        }
+    }
+    if let Some(start) = option.start {
+        g[0][0] = start;
+    }
+    if let Some(end) = option.end {
+        g[2 * (r - 1)][2 * (c - 1)] = end;
+    }
+    g
+}
+
+/// 随机DFS：每次从栈顶的房间随机走向一个未访问的相邻房间，走投无路时回溯，通道较长
+fn dfs(adj: &[Vec<usize>]) -> Vec<(usize, usize)> {
+    let mut visited = vec![false; adj.len()];
+    let mut stack = vec![0];
+    let mut edges = Vec::new();
+    visited[0] = true;
+    while let Some(&u) = stack.last() {
+        let next: Vec<usize> = adj[u].iter().cloned().filter(|&v| !visited[v]).collect();
+        match with_rng(|rng| next.choose(rng).cloned()) {
+            Some(v) => {
+                visited[v] = true;
+                edges.push((u, v));
+                stack.push(v);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    edges
+}
+
+/// 随机Prim：每次从已访问区域的边界上随机打通一面墙，分叉较多、死路较短
+fn prim(adj: &[Vec<usize>]) -> Vec<(usize, usize)> {
+    let mut visited = vec![false; adj.len()];
+    let mut frontier: Vec<(usize, usize)> = adj[0].iter().map(|&v| (0, v)).collect();
+    let mut edges = Vec::new();
+    visited[0] = true;
+    while !frontier.is_empty() {
+        let i = random_range!(0, frontier.len() - 1);
+        let (u, v) = frontier.swap_remove(i);
+        if visited[v] {
+            continue;
+        }
+        visited[v] = true;
+        edges.push((u, v));
+        frontier.extend(adj[v].iter().filter(|&&w| !visited[w]).map(|&w| (v, w)));
+    }
+    edges
+}
+
+/// 随机Kruskal：以随机顺序考虑每面墙，两侧的房间尚不连通时打通
+/// 并查集按秩合并，查找时路径减半，都不使用递归
+fn kruskal(size: usize, mut walls: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    fn find(parent: &mut [usize], mut x: usize) -> usize {
+        while parent[x] != x {
+            parent[x] = parent[parent[x]];
+            x = parent[x];
+        }
+        x
+    }
+    let mut parent: Vec<usize> = (0..size).collect();
+    let mut rank = vec![0u8; size];
+    with_rng(|rng| walls.shuffle(rng));
+    walls
+        .into_iter()
+        .filter(|&(u, v)| {
+            let (mut a, mut b) = (find(&mut parent, u), find(&mut parent, v));
+            if a == b {
+                return false;
+            }
+            if rank[a] > rank[b] {
+                std::mem::swap(&mut a, &mut b);
+            }
+            parent[a] = b;
+            if rank[a] == rank[b] {
+                rank[b] += 1;
+            }
+            true
+        })
+        .collect()
+}
+
+/// 把字符网格按行输出，同一行的格子之间用sep分隔
+pub fn render(g: &[Vec<char>], sep: &str) -> String {
+    g.iter()
+        .map(|row| {
+            let row: Vec<String> = row.iter().map(|c| c.to_string()).collect();
+            row.join(sep)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::IntParameter;
+
+    fn option(algo: MazeAlgo) -> MazeOption {
+        MazeOption {
+            algo,
+            wall: '#',
+            path: '.',
+            start: None,
+            end: None,
+            extra: IntParameter::Confirm(0),
+            sep: None,
+        }
+    }
+
+    /// 通道格子的数目、相邻通道对的数目，以及从左上角能到达的通道格子数目
+    fn count(g: &[Vec<char>]) -> (usize, usize, usize) {
+        let (n, m) = (g.len(), g[0].len());
+        let free = |x: usize, y: usize| g[x][y] == '.';
+        let (mut cells, mut edges) = (0, 0);
+        for x in 0..n {
+            for y in 0..m {
+                if free(x, y) {
+                    cells += 1;
+                    edges += (x + 1 < n && free(x + 1, y)) as usize;
+                    edges += (y + 1 < m && free(x, y + 1)) as usize;
+                }
+            }
+        }
+        let mut visited = vec![vec![false; m]; n];
+        let mut stack = vec![(0usize, 0usize)];
+        visited[0][0] = true;
+        let mut reached = 0;
+        while let Some((x, y)) = stack.pop() {
+            reached += 1;
+            let next = [
+                (x + 1, y),
+                (x, y + 1),
+                (x.wrapping_sub(1), y),
+                (x, y.wrapping_sub(1)),
+            ];
+            for &(nx, ny) in next.iter() {
+                if nx < n && ny < m && free(nx, ny) && !visited[nx][ny] {
+                    visited[nx][ny] = true;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+        (cells, edges, reached)
+    }
+
+    /// 从from出发只经过空地能否到达to
+    fn reachable(g: &[Vec<char>], from: (usize, usize), to: (usize, usize)) -> bool {
+        let (n, m) = (g.len(), g[0].len());
+        let mut visited = vec![vec![false; m]; n];
+        let mut stack = vec![from];
+        while let Some((x, y)) = stack.pop() {
+            if x >= n || y >= m || visited[x][y] || g[x][y] != '.' {
+                continue;
+            }
+            visited[x][y] = true;
+            stack.extend(
+                [
+                    (x + 1, y),
+                    (x, y + 1),
+                    (x.wrapping_sub(1), y),
+                    (x, y.wrapping_sub(1)),
+                ]
+                .iter(),
+            );
+        }
+        visited[to.0][to.1]
+    }
+
+    #[test]
+    fn test_grid_path() {
+        let chars = ['.', '#'];
+        for &density in [50, 100].iter() {
+            let g = grid(30, 40, &chars, Some(density), Some([(1, 1), (30, 40)]));
+            assert!(reachable(&g, (0, 0), (29, 39)));
+            let g = grid(30, 40, &chars, Some(density), Some([(5, 17), (22, 3)]));
+            assert!(reachable(&g, (4, 16), (21, 2)));
+        }
+        let g = grid(30, 40, &chars, Some(100), None);
+        assert!(g.iter().flatten().all(|&c| c == '#'));
+    }
+
+    #[test]
+    fn test_perfect_maze() {
+        for &algo in [MazeAlgo::Dfs, MazeAlgo::Prim, MazeAlgo::Kruskal].iter() {
+            for &(n, m) in [(1, 1), (1, 10), (9, 21), (20, 15), (101, 101)].iter() {
+                let (cells, edges, reached) = count(&maze(n, m, &option(algo), 0));
+                assert_eq!(reached, cells);
+                assert_eq!(edges + 1, cells); // 连通且无环
+            }
+        }
+    }
+
+    #[test]
+    fn test_braided_maze() {
+        for &algo in [MazeAlgo::Dfs, MazeAlgo::Prim, MazeAlgo::Kruskal].iter() {
+            let (cells, edges, reached) = count(&maze(31, 41, &option(algo), 20));
+            assert_eq!(reached, cells);
+            assert_eq!(edges + 1, cells + 20);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::details::error_info;
+use std::{
+    cell::RefCell,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+thread_local! {
+    static FILES: RefCell<Vec<PathBuf>> = RefCell::default(); // 正在解析的模板文件，最后一项为当前文件
+    static CONTENTS: RefCell<Vec<String>> = RefCell::default(); // 读取过的文件的内容，计入清单的哈希
+}
+
+pub fn enter(path: &Path) {
+    FILES.with(|f| {
+        let mut f = f.borrow_mut();
+        let canonical = |p: &Path| fs::canonicalize(p).unwrap_or(p.to_path_buf());
+        if f.iter().any(|p| canonical(p) == canonical(path)) {
+            let mut chain: Vec<String> = f.iter().map(|p| p.display().to_string()).collect();
+            chain.push(path.display().to_string());
+            error_info(&format!("Cyclic #include: {}", chain.join(" -> ")));
+        }
+        f.push(path.to_path_buf());
+    });
+}
+
+pub fn leave() {
+    FILES.with(|f| {
+        f.borrow_mut().pop();
+    });
+}
+
+/// 以file为当前文件执行f，用于展开宏时让宏体中的相对路径相对于定义宏的文件
+/// 宏可能在定义它的文件中展开，因此不检测循环引用
+pub fn within<T>(file: Option<PathBuf>, f: impl FnOnce() -> T) -> T {
+    let file = match file {
+        Some(file) => file,
+        None => return f(),
+    };
+    FILES.with(|f| f.borrow_mut().push(file));
+    let res = f();
+    leave();
+    res
+}
+
+/// 当前正在解析的文件，用于在报错时指出出错的文件
+pub fn current() -> Option<PathBuf> {
+    FILES.with(|f| f.borrow().last().cloned())
+}
+
+/// 相对路径相对于当前文件所在的文件夹
+pub fn resolve(path: &str) -> PathBuf {
+    match current().as_ref().and_then(|p| p.parent()) {
+        Some(dir) => dir.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+/// rest在content中的起始位置对应的行号和列号，都从1开始
+pub fn position(content: &str, rest: &str) -> (usize, usize) {
+    let before = &content[..content.len() - rest.len()];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+pub fn read(path: &Path) -> String {
+    let content = fs::read_to_string(path).unwrap_or_else(|e| match e.kind() {
+        ErrorKind::NotFound => {
+            error_info(&format!("Cannot find the included file {}", path.display()))
+        }
+        ErrorKind::InvalidData => error_info(&format!(
+            "The included file {} was not valid UTF-8 file",
+            path.display()
+        )),
+        _ => error_info(&format!(
+            "Some unknown error occurred while reading the included file {}",
+            path.display()
+        )),
+    });
+    CONTENTS.with(|c| c.borrow_mut().push(content.clone()));
+    content
+}
+
+/// 到目前为止读取过的被引用文件的内容
+pub fn contents() -> Vec<String> {
+    CONTENTS.with(|c| c.borrow().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let content = "def f() = 1\n\n  i[1,\n";
+        assert_eq!(position(content, content), (1, 1));
+        assert_eq!(position(content, &content[15..]), (3, 3));
+        assert_eq!(position(content, ""), (4, 1));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    details::{error_info, warning_info},
+    include,
+    token::Token,
+};
+use std::{cell::RefCell, collections::HashMap, path::PathBuf};
+
+/// 用`def name(a, b) = ...`定义的宏，宏体以源码形式保存，第一次以某组实参调用时展开解析
+#[derive(Clone, Debug)]
+pub struct Macro {
+    pub params: Vec<String>,
+    pub body: String,
+    pub file: Option<PathBuf>, // 定义宏的文件
+}
+
+/// 以宏名和实参为键缓存展开的结果
+type Expansions = HashMap<(String, Vec<String>), Vec<Token>>;
+
+thread_local! {
+    static MACROS: RefCell<HashMap<String, Macro>> = RefCell::new(HashMap::new());
+    static EXPANDING: RefCell<Vec<String>> = RefCell::default(); // 正在展开的宏，用于检测递归
+    static EXPANSIONS: RefCell<Expansions> = RefCell::new(HashMap::new());
+}
+
+/// 内置变量不能作形参，否则宏体中的`$n`无法区分是形参还是循环次数
+const BUILTIN_VARIABLES: &[&str] = &["i", "n", "fileid", "filepos", "filecount"];
+
+/// 关键字和内置生成器、函数的名字，不能用作宏名
+#[rustfmt::skip]
+pub static RESERVED: &[&str] = &[
+    "def", "if", "else",
+    "min", "max", "abs", "pow", "div", "pick", "sample",
+    "grid", "maze", "geo", "interval",
+    "prime", "composite", "semiprime", "hcn", "carmichael", "pow2",
+];
+
+pub fn define(name: String, params: Vec<String>, body: String) {
+    if RESERVED.contains(&name.as_str()) {
+        error_info(&format!(
+            "'{}' is reserved and cannot be a macro name",
+            name
+        ));
+    }
+    for p in params.iter() {
+        if BUILTIN_VARIABLES.contains(&p.as_str()) {
+            error_info(&format!(
+                "'{}' is a built-in variable and cannot be a parameter of macro '{}'",
+                p, name
+            ));
+        }
+    }
+    let file = include::current();
+    EXPANSIONS.with(|e| e.borrow_mut().clear()); // 重新定义的宏可能被其它宏调用
+    MACROS.with(|m| {
+        if m.borrow_mut()
+            .insert(name.clone(), Macro { params, body, file })
+            .is_some()
+        {
+            warning_info(&format!("Macro '{}' is redefined", name));
+        }
+    });
+}
+
+pub fn is_defined(name: &str) -> bool {
+    MACROS.with(|m| m.borrow().contains_key(name))
+}
+
+pub fn cached(name: &str, args: &[String]) -> Option<Vec<Token>> {
+    EXPANSIONS.with(|e| e.borrow().get(&(name.to_string(), args.to_vec())).cloned())
+}
+
+pub fn cache(name: &str, args: &[String], tokens: &[Token]) {
+    EXPANSIONS.with(|e| {
+        e.borrow_mut()
+            .insert((name.to_string(), args.to_vec()), tokens.to_vec())
+    });
+}
+
+/// 所有宏定义的源码，按宏名排序，计入清单的哈希
+pub fn definitions() -> Vec<String> {
+    MACROS.with(|m| {
+        let mut v: Vec<String> = m
+            .borrow()
+            .iter()
+            .map(|(k, d)| format!("{}({})={}", k, d.params.join(","), d.body))
+            .collect();
+        v.sort();
+        v
+    })
+}
+
+pub fn file(name: &str) -> Option<PathBuf> {
+    MACROS.with(|m| m.borrow().get(name)?.file.clone())
+}
+
+/// 把宏体中的`$param`替换为实参的源码，得到展开后的源码
+pub fn expand(name: &str, args: &[String]) -> String {
+    let m = MACROS
+        .with(|m| m.borrow().get(name).cloned())
+        .unwrap_or_else(|| {
+            error_info(&format!("Macro '{}' is not defined", name));
+        });
+    if m.params.len() != args.len() {
+        error_info(&format!(
+            "Macro '{}' expects {} arguments, but {} were given",
+            name,
+            m.params.len(),
+            args.len()
+        ));
+    }
+    let mut s = String::new();
+    let mut rest = m.body.as_str();
+    while let Some(pos) = rest.find('$') {
+        s.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        match m.params.iter().position(|p| p == &rest[..len]) {
+            Some(i) => {
+                s.push_str(&wrap(&args[i]));
+                rest = &rest[len..];
+            }
+            None => s.push('$'), // 不是形参，保留为变量
+        }
+    }
+    s.push_str(rest);
+    s
+}
+
+/// 字面量原样替换，其余的实参加上括号，避免替换后改变运算顺序
+fn wrap(arg: &str) -> String {
+    let literal = arg.starts_with('"') && arg.ends_with('"')
+        || arg.starts_with('\'') && arg.ends_with('\'')
+        || arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+        || arg.starts_with('$')
+            && arg[1..]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if literal {
+        arg.to_string()
+    } else {
+        format!("({})", arg)
+    }
+}
+
+pub fn enter(name: &str) {
+    EXPANDING.with(|e| {
+        let mut e = e.borrow_mut();
+        if e.iter().any(|n| n == name) {
+            let mut chain = e.clone();
+            chain.push(name.to_string());
+            error_info(&format!(
+                "Macro '{}' is recursive: {}",
+                name,
+                chain.join(" -> ")
+            ));
+        }
+        e.push(name.to_string());
+    });
+}
+
+pub fn leave() {
+    EXPANDING.with(|e| {
+        e.borrow_mut().pop();
+    });
+}
+
+/// 从buf开头截取用括号括住、用逗号分隔的实参，括号、引号内的逗号不作分隔
+pub fn split_args(buf: &mut &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 1;
+    let mut escaped = false;
+    if !buf.starts_with('(') {
+        return None;
+    }
+    for (i, c) in buf.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(_), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') | (None, '{') => depth += 1,
+            (None, ')') if depth == 0 => {
+                let last = buf[start..i].trim();
+                if !(last.is_empty() && args.is_empty()) {
+                    args.push(last.to_string());
+                }
+                *buf = &buf[i + 1..];
+                return Some(args);
+            }
+            (None, ')') | (None, ']') | (None, '}') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                args.push(buf[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    None
+}
+
+/// 截取宏体：以`{`开头时截取到与之匹配的`}`，否则截取到行末
+pub fn take_body<'a>(buf: &mut &'a str) -> &'a str {
+    let end = if buf.starts_with('{') {
+        let mut depth = 0;
+        let mut quote = false;
+        let mut end = buf.len();
+        let mut prev = ' ';
+        for (i, c) in buf.char_indices() {
+            match c {
+                '"' if prev != '\\' => quote = !quote,
+                '{' if !quote => depth += 1,
+                '}' if !quote => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = i + 1;
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            prev = c;
+        }
+        end
+    } else {
+        buf.find('\n').unwrap_or(buf.len())
+    };
+    let body = &buf[..end];
+    *buf = &buf[end..];
+    body
+}
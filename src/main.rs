@@ -1,18 +1,43 @@
 #![feature(iterator_fold_self)]
+mod antihash;
 mod details;
+mod export;
+mod format;
+mod geometry;
+mod grid;
+mod include;
+mod macros;
+mod manifest;
+mod naming;
+mod number;
 mod parser;
 mod random;
+mod regex;
+mod subtask;
 mod token;
+mod variable;
 use crate::{
-    details::{error_info, GetParameter, Ignore},
-    parser::{config, file_range, token},
+    details::{error_info, warning_info, GetParameter, Ignore},
+    export::{export, Case, FORMATS},
+    format::Format,
+    manifest::{
+        canonical_config, hash_file, hash_of, hash_section, take_evaluated, Entry, Manifest,
+    },
+    naming::{default_output, file_name, DEFAULT_INPUT},
+    parser::{
+        config, definition, file_range, flush_warnings, include_token, raw_config, statement,
+        subtask_mark,
+    },
+    random::reseed,
+    subtask::{Mark, Subtasks},
     token::{Config, Token},
+    variable::with_bindings,
 };
 use colour::*;
 use powershell_script;
 use simple_combinators::{
-    combinator::{attempt, preview},
-    parser::string,
+    combinator::{attempt, many, optional, preview},
+    parser::{spaces, string},
     ParseError, Parser,
 };
 use std::{
@@ -28,12 +53,16 @@ fn pause() {
     io::stdin().read_line(&mut String::new()).ignore();
 }
 
-fn parse_once(buf: &mut &str, is_first: bool) -> (Range<usize>, Vec<Token>, bool) {
+fn parse_once(buf: &mut &str, is_first: bool) -> (Range<usize>, Option<Mark>, Vec<Token>, bool) {
     // 解析一个文件标注和其对应的模板
     let range = file_range().parse(buf);
     let mut end = false;
+    let mut mark = None;
     let range = match range {
-        Ok(r) => r,
+        Ok(r) => {
+            mark = optional(attempt(subtask_mark())).parse(buf).ignore();
+            r
+        }
         Err(_) => {
             end = true;
             if is_first {
@@ -43,9 +72,15 @@ fn parse_once(buf: &mut &str, is_first: bool) -> (Range<usize>, Vec<Token>, bool
             }
         } // 没有发现文件标注，如果尚未生成过，则默认生成1.in~10.in
     };
-    let results = attempt(token()).iter(buf).with_result().collect();
+    let results = attempt(statement()).iter(buf).with_result().collect();
     let tokens = handle_parse_result(results);
-    (range, tokens, end)
+    (range, mark, tokens, end)
+}
+
+/// 一段模板解析得到的生成器，以及用于增量生成的哈希
+struct Section {
+    tokens: Vec<Token>,
+    hash: u64,
 }
 
 fn handle_parse_result(results: Vec<Result<Token, ParseError>>) -> Vec<Token> {
@@ -58,56 +93,161 @@ fn handle_parse_result(results: Vec<Result<Token, ParseError>>) -> Vec<Token> {
         e_white_ln!("\"{}\"", err.position);
         exit(1);
     }
+    flush_warnings();
     results[..results.len() - 1]
         .iter()
         .map(|r| r.as_ref().unwrap().clone())
         .collect()
 }
 
-fn parse_and_generate(mut buf: &str, folder: PathBuf, config: &Config) {
+fn parse_and_generate(
+    mut buf: &str,
+    folder: PathBuf,
+    config: &Config,
+    settings: &[String],
+    format: &Format,
+    force: bool,
+) {
+    let mut manifest = if force {
+        Manifest::default() // 强制重新生成时忽略已有的清单
+    } else {
+        Manifest::load(&folder)
+    };
+    // 第一个文件标注之前的宏定义和#include，如果之后没有文件标注，则归入默认生成的文件
+    let mut preamble: Vec<Token> = many(attempt(spaces().with(definition().or(include_token()))))
+        .parse(&mut buf)
+        .ignore();
+    let mut subtasks = Subtasks::default();
+    let mut cases = Vec::new();
     let mut is_first = true;
+    let mut start = buf;
     loop {
-        let (range, tokens, end) = parse_once(&mut buf, is_first);
+        let (range, mark, mut tokens, end) = parse_once(&mut buf, is_first);
+        // 配置、宏定义、被引用的文件、这段模板的源码和其中求出的`!`参数都会影响生成结果
+        let mut parts = settings.to_vec();
+        parts.extend(macros::definitions());
+        parts.extend(include::contents());
+        parts.push(start[..start.len() - buf.len()].to_string());
+        parts.extend(take_evaluated());
+        let hash = hash_section(&parts);
+        start = buf;
+        if is_first && end {
+            tokens.splice(0..0, preamble.drain(..));
+        } else if is_first && preamble.iter().any(|t| !t.is_empty_group()) {
+            warning_info("Tokens included before the first file range are ignored");
+        }
         is_first = false;
-        for i in range {
-            generate(i, &tokens, &folder, config);
+        let section = Section { tokens, hash };
+        if let Some(mark) = mark {
+            subtasks.add(mark, range.clone());
+        }
+        for i in range.clone() {
+            let subtask = subtasks.find(i).map(|s| s.name.as_str());
+            // 当前文件的编号、在文件标注中的位置（从1开始）和文件标注包含的文件数
+            let bindings = [
+                ("fileid", i as i64),
+                ("filepos", (i - range.start + 1) as i64),
+                ("filecount", range.len() as i64),
+            ];
+            cases.push(with_bindings(&bindings, || {
+                generate(i, subtask, &section, &folder, config, format, &mut manifest)
+            }));
         }
         if end {
             break;
         }
     }
+    manifest.save(&folder);
+    if !subtasks.is_empty() {
+        subtasks.check();
+        subtasks.write(&folder);
+    }
+    for format in config.get_enums("export").unwrap_or_default() {
+        if FORMATS.contains(&format.as_str()) {
+            export(&format, &folder, &cases, &subtasks);
+        } else {
+            warning_info(&format!("unsupported export format: {}", format));
+        }
+    }
     println!("Finished!");
 }
 
-fn generate(fileid: usize, tokens: &Vec<Token>, folder: &PathBuf, config: &Config) {
+fn generate(
+    fileid: usize,
+    subtask: Option<&str>,
+    section: &Section,
+    folder: &PathBuf,
+    config: &Config,
+    format: &Format,
+    manifest: &mut Manifest,
+) -> Case {
     let prefix = config.get_str("prefix").unwrap_or(String::new());
-    let filename = format!("{}{}.in", prefix, fileid);
-    println!("Generating {}", filename);
+    let pattern = config.get_str("name").unwrap_or(DEFAULT_INPUT.to_string());
+    let filename = file_name(&pattern, &prefix, fileid, subtask);
     let target = folder.join(&filename);
-    let mut s = String::new();
-    for i in tokens.iter() {
-        s.push_str(&i.generate_str().unwrap_or_else(|| {
-            error_info(&format!(
-                "Something went wrong while generating {}",
-                filename
-            ))
-        }));
+    let old = manifest.get(fileid).copied();
+    let seed = match (config.get_int("seed"), old) {
+        (Some(seed), _) => hash_of(format!("{} {}", seed, fileid).as_bytes()),
+        (None, Some(e)) if e.tokens == section.hash => e.seed, // 沿用上次的种子，使未改动的文件可以跳过
+        (None, _) => rand::random(),
+    };
+    let std = config.get_str("std");
+    let std_hash = std.as_ref().and_then(|std| hash_file(Path::new(std)));
+    let unchanged =
+        matches!(old, Some(e) if e.tokens == section.hash && e.seed == seed) && target.exists();
+    if unchanged {
+        println!("Skipping {} (unchanged)", filename);
+    } else {
+        println!("Generating {}", filename);
+        reseed(seed);
+        let mut s = String::new();
+        for i in section.tokens.iter() {
+            s.push_str(&i.generate_str(&format.sep).unwrap_or_else(|| {
+                error_info(&format!(
+                    "Something went wrong while generating {}",
+                    filename
+                ))
+            }));
+        }
+        fs::write(&target, format.normalize(s)).unwrap_or_else(|err| match err {
+            _ if err.kind() == ErrorKind::PermissionDenied => {
+                error_info("Permission denied while trying to write generated results to the file")
+            }
+            _ => error_info(
+                "Some unknown error occurred while trying to write generated results to the file",
+            ),
+        });
     }
-    fs::write(&target, s).unwrap_or_else(|err| match err {
-        _ if err.kind() == ErrorKind::PermissionDenied => {
-            error_info("Permission denied while trying to write generated results to the file")
+    let mut case = Case {
+        id: fileid,
+        input: filename,
+        output: None,
+    };
+    if let Some(std) = std {
+        let output = match config.get_str("outname") {
+            Some(pattern) => file_name(&pattern, &prefix, fileid, subtask),
+            None => default_output(&case.input),
+        };
+        let input = &case.input;
+        let std_unchanged = std_hash.is_some() && old.and_then(|e| e.std) == std_hash;
+        if unchanged && std_unchanged && folder.join(&output).exists() {
+            println!("Skipping {} (unchanged)", output);
+        } else {
+            run_std(&folder, &output, &input, &std, format);
         }
-        _ => error_info(
-            "Some unknown error occurred while trying to write generated results to the file",
-        ),
-    });
-    if let Some(std) = config.get_str("std") {
-        let output = format!("{}{}.out", prefix, fileid);
-        let input = &filename;
-        run_std(&folder, &output, &input, &std);
+        case.output = Some(output);
     }
+    manifest.insert(
+        fileid,
+        Entry {
+            tokens: section.hash,
+            seed,
+            std: std_hash,
+        },
+    );
+    case
 }
-fn run_std(folder: &PathBuf, output: &str, input: &str, std: &str) {
+fn run_std(folder: &PathBuf, output: &str, input: &str, std: &str, format: &Format) {
     println!("Generating {}", output);
     let std_path = Path::new(std);
     let std_parent = std_path
@@ -130,12 +270,29 @@ fn run_std(folder: &PathBuf, output: &str, input: &str, std: &str) {
             input, script
         ))
     });
+    // 输出文件同样按#format处理，但分隔符只与生成的整数有关，行末只删除空白
+    let format = Format {
+        sep: String::from(" "),
+        ..format.clone()
+    };
+    let path = folder.join(output);
+    if let Ok(s) = fs::read_to_string(&path) {
+        fs::write(&path, format.normalize(s)).unwrap_or_else(|_| {
+            error_info(&format!(
+                "Some unknown error occurred while trying to write {}",
+                output
+            ))
+        });
+    }
 }
 
 fn get_template<'a>() -> (PathBuf, String) {
-    let args: Vec<String> = env::args().collect();
-    let path = if args.len() > 1 {
-        &args[1]
+    let args: Vec<String> = env::args()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    let path = if !args.is_empty() {
+        &args[0]
     } else {
         "template.txt" // 默认路径
     };
@@ -180,11 +337,79 @@ fn get_folder(template: &PathBuf, config: &Config) -> PathBuf {
 
 fn main() {
     let (path, template) = get_template();
+    include::enter(&path);
     let mut buf = template.as_str();
+    let raw = raw_config(buf);
+    let seed = raw.iter().find(|(k, _)| k == "seed");
+    if let Some(Ok(seed)) = seed.map(|(_, v)| v.trim().parse::<i64>()) {
+        reseed(seed as u64); // 在解析配置之前重置，使配置和模板中的`!`参数都可复现
+    }
     let config = config().parse(&mut buf).ignore(); // 解析配置
+    let mut settings = canonical_config(&raw);
+    settings.extend(take_evaluated());
+    for key in ["name", "outname"].iter() {
+        match config.get_str(key) {
+            Some(pattern) if !pattern.contains("{id") => warning_info(&format!(
+                "The pattern of '{}' does not contain {{id}}, files will overwrite each other",
+                key
+            )),
+            _ => {}
+        }
+    }
     let folder = get_folder(&path, &config);
-    parse_and_generate(buf, folder, &config);
+    let force = env::args().any(|a| a == "--force");
+    let format = Format::from_config(&config);
+    parse_and_generate(buf, folder, &config, &settings, &format, force);
     if config.get_bool("pause").unwrap_or(false) {
         pause();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在临时文件夹中按模板生成1.in，返回其内容
+    fn run(name: &str, template: &str) -> String {
+        let folder = env::temp_dir().join(format!("pdg-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let mut buf = template;
+        let config = config().parse(&mut buf).ignore();
+        let format = Format::from_config(&config);
+        parse_and_generate(buf, folder.clone(), &config, &[], &format, true);
+        let s = fs::read_to_string(folder.join("1.in")).unwrap();
+        fs::remove_dir_all(&folder).ok();
+        s
+    }
+
+    #[test]
+    fn test_antihash_modulus() {
+        let m: u128 = (1 << 61) - 1;
+        let s = run("antihash", ":> 1\ns[antihash,131,2305843009213693951]\n");
+        let v: Vec<&str> = s.lines().collect();
+        let hash = |s: &str| s.bytes().fold(0, |h, c| (h * 131 + c as u128) % m);
+        assert_eq!(v.len(), 2);
+        assert_ne!(v[0], v[1]);
+        assert_eq!(hash(v[0]), hash(v[1]));
+    }
+
+    #[test]
+    fn test_large_bounds() {
+        assert_eq!(
+            run("literal", "9007199254740993").trim(),
+            "9007199254740993"
+        );
+        let ranges = [
+            (1_000_000_000_000_000_000, 1_000_000_000_000_000_010),
+            (1_000_000_000_000_000_000, 1_000_000_000_000_000_100),
+            (9_223_372_036_854_775_000, i64::MAX),
+        ];
+        for &(l, r) in ranges.iter() {
+            for kind in ["prime", "composite", "semiprime"].iter() {
+                let template = format!("{}[{}, {}]", kind, l, r);
+                let x: i64 = run(kind, &template).trim().parse().unwrap();
+                assert!(l <= x && x <= r, "{} gave {}", template, x);
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{details::warning_info, token::Parameter};
+use std::{cell::RefCell, collections::BTreeMap, fs, path::Path};
+
+static MANIFEST: &str = ".manifest";
+/// 不影响生成的.in文件的配置，不计入哈希
+static IGNORED: &[&str] = &["pause", "std", "export"];
+
+thread_local! {
+    static EVALUATED: RefCell<Vec<String>> = RefCell::default(); // 解析模板时求出的`!`参数
+}
+
+/// 记录生成某个文件时的输入，用于判断再次运行时能否跳过
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub tokens: u64,
+    pub seed: u64,
+    pub std: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    entries: BTreeMap<usize, Entry>,
+}
+
+impl Manifest {
+    pub fn load(folder: &Path) -> Manifest {
+        let mut manifest = Manifest::default();
+        let content = match fs::read_to_string(folder.join(MANIFEST)) {
+            Ok(content) => content,
+            Err(_) => return manifest, // 没有清单（或无法读取）时视为全部需要重新生成
+        };
+        for line in content.lines().filter(|l| !l.starts_with('#')) {
+            match parse_entry(line) {
+                Some((id, entry)) => {
+                    manifest.entries.insert(id, entry);
+                }
+                None => {
+                    warning_info("The manifest was malformed, all files will be regenerated");
+                    return Manifest::default();
+                }
+            }
+        }
+        manifest
+    }
+    pub fn save(&self, folder: &Path) {
+        let mut s = String::from("# id tokens seed std\n");
+        for (id, e) in self.entries.iter() {
+            let std = e.std.map_or(String::from("-"), |h| format!("{:016x}", h));
+            s.push_str(&format!(
+                "{} {:016x} {:016x} {}\n",
+                id, e.tokens, e.seed, std
+            ));
+        }
+        if fs::write(folder.join(MANIFEST), s).is_err() {
+            warning_info("Failed to write the manifest, the next run will regenerate all files");
+        }
+    }
+    pub fn get(&self, id: usize) -> Option<&Entry> {
+        self.entries.get(&id)
+    }
+    pub fn insert(&mut self, id: usize, entry: Entry) {
+        self.entries.insert(id, entry);
+    }
+}
+
+fn parse_entry(line: &str) -> Option<(usize, Entry)> {
+    let v: Vec<&str> = line.split_whitespace().collect();
+    match &v[..] {
+        [id, tokens, seed, std] => Some((
+            id.parse().ok()?,
+            Entry {
+                tokens: u64::from_str_radix(tokens, 16).ok()?,
+                seed: u64::from_str_radix(seed, 16).ok()?,
+                std: match *std {
+                    "-" => None,
+                    h => Some(u64::from_str_radix(h, 16).ok()?),
+                },
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// 64位FNV-1a哈希。清单要在不同版本的程序间沿用，由#seed派生的种子也要保持不变，
+/// 因此不用算法未指定的DefaultHasher
+pub fn hash_of(bytes: &[u8]) -> u64 {
+    feed(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+fn feed(h: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(h, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// 记录解析时求出的`!`参数。未指定#seed时它们每次运行都不同，计入哈希后对应的文件会重新生成
+pub fn record(p: &Parameter) {
+    let s = match (p.int(), p.str()) {
+        (Some(i), _) => i.to_string(),
+        (None, Some(s)) => format!("{}:{}", s.len(), s),
+        (None, None) => String::new(),
+    };
+    EVALUATED.with(|e| e.borrow_mut().push(s));
+}
+
+/// 已经记录的`!`参数的个数
+pub fn evaluated() -> usize {
+    EVALUATED.with(|e| e.borrow().len())
+}
+
+/// 取出并清空已经记录的`!`参数
+pub fn take_evaluated() -> Vec<String> {
+    EVALUATED.with(|e| e.borrow_mut().drain(..).collect())
+}
+
+/// 配置的规范形式：去掉不影响生成结果的项，按名字排序（同名的项以后出现的为准）
+pub fn canonical_config(raw: &[(String, String)]) -> Vec<String> {
+    let map: BTreeMap<&str, &str> = raw
+        .iter()
+        .filter(|(k, _)| !IGNORED.contains(&k.as_str()))
+        .map(|(k, v)| (k.as_str(), v.trim()))
+        .collect();
+    map.iter().map(|(k, v)| format!("#{}[{}]", k, v)).collect()
+}
+
+/// 计算一段模板的哈希，各部分都是源码或求出的值，与程序内部的数据结构无关
+/// 每部分前加上长度，避免不同的划分拼接成相同的字节串
+pub fn hash_section(parts: &[String]) -> u64 {
+    parts.iter().fold(hash_of(b""), |h, p| {
+        feed(feed(h, format!("{}:", p.len()).as_bytes()), p.as_bytes())
+    })
+}
+
+pub fn hash_file(path: &Path) -> Option<u64> {
+    fs::read(path).ok().map(|bytes| hash_of(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_of() {
+        // FNV-1a的标准测试向量
+        assert_eq!(hash_of(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_of(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash_of(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_hash_section() {
+        let parts = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            hash_section(&parts(&["a", "b"])),
+            hash_section(&parts(&["a", "b"]))
+        );
+        assert_ne!(
+            hash_section(&parts(&["ab", ""])),
+            hash_section(&parts(&["a", "b"]))
+        );
+        let raw = |v: &[(&str, &str)]| {
+            v.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            canonical_config(&raw(&[("seed", " 1 "), ("pause", ""), ("folder", "\"d\"")])),
+            vec!["#folder[\"d\"]", "#seed[1]"]
+        );
+    }
+
+    #[test]
+    fn test_entry() {
+        let (id, e) = parse_entry("3 00000000000000ff 0000000000000001 -").unwrap();
+        assert_eq!(id, 3);
+        assert_eq!(
+            e,
+            Entry {
+                tokens: 255,
+                seed: 1,
+                std: None
+            }
+        );
+        assert!(parse_entry("3 ff 1").is_none());
+        assert!(parse_entry("3 ff 1 xyz").is_none());
+    }
+}
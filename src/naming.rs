@@ -0,0 +1,44 @@
+use crate::details::warning_info;
+
+pub static DEFAULT_INPUT: &str = "{prefix}{id}.in";
+
+/// 按照形如`{prefix}{id:02}.in`的模式生成文件名
+pub fn file_name(pattern: &str, prefix: &str, id: usize, subtask: Option<&str>) -> String {
+    let mut s = String::new();
+    let mut rest = pattern;
+    while let Some(l) = rest.find('{') {
+        s.push_str(&rest[..l]);
+        let r = match rest[l..].find('}') {
+            Some(r) => l + r,
+            None => break,
+        };
+        let placeholder = &rest[l + 1..r];
+        match placeholder {
+            "prefix" => s.push_str(prefix),
+            "id" => s.push_str(&id.to_string()),
+            "subtask" => s.push_str(subtask.unwrap_or("")),
+            _ if placeholder.starts_with("id:") => match placeholder[3..].parse::<usize>() {
+                Ok(width) => s.push_str(&format!("{:0width$}", id, width = width)),
+                Err(_) => {
+                    warning_info(&format!("Invalid zero padding in '{{{}}}'", placeholder));
+                    s.push_str(&id.to_string());
+                }
+            },
+            _ => {
+                warning_info(&format!("unsupported placeholder: {{{}}}", placeholder));
+                s.push_str(&rest[l..=r]);
+            }
+        }
+        rest = &rest[r + 1..];
+    }
+    s.push_str(rest);
+    s
+}
+
+/// 未指定输出文件名时，把输入文件名的`.in`扩展名换成`.out`
+pub fn default_output(input: &str) -> String {
+    match input.strip_suffix(".in") {
+        Some(stem) => format!("{}.out", stem),
+        None => format!("{}.out", input),
+    }
+}
@@ -0,0 +1,289 @@
+use crate::{details::error_info, random_range, token::NumberKind};
+
+/// 小于37时直接判断，否则以这些数为底的Miller-Rabin测试对2^64以内的数都是确定的
+static BASES: &[u64] = &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+/// 前15个质数，乘积已超过2^63
+static PRIMES: &[i64] = &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+
+/// 按kind在[l, r]中生成一个数，hcn只使用r
+pub fn number(kind: NumberKind, l: i64, r: i64) -> i64 {
+    match kind {
+        NumberKind::Prime => choose(l, r, "prime", is_prime),
+        NumberKind::Composite => choose(l, r, "composite number", |x| x >= 4 && !is_prime(x)),
+        NumberKind::Semiprime => semiprime(l, r),
+        NumberKind::HighlyComposite => highly_composite(r),
+        NumberKind::Carmichael => carmichael(l, r),
+        NumberKind::Pow2 => {
+            let k: Vec<u32> = (0..63).filter(|&k| l <= 1 << k && 1 << k <= r).collect();
+            if k.is_empty() {
+                error_info(&format!("There is no power of 2 between {} and {}", l, r));
+            }
+            1 << k[random_range!(0, k.len() - 1)]
+        }
+    }
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut a: u64, mut b: u64, m: u64) -> u64 {
+    let mut res = 1;
+    while b > 0 {
+        if b & 1 == 1 {
+            res = mul_mod(res, a, m);
+        }
+        a = mul_mod(a, a, m);
+        b >>= 1;
+    }
+    res
+}
+
+pub fn is_prime(n: i64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for &p in BASES.iter() {
+        if n % p as i64 == 0 {
+            return n == p as i64;
+        }
+    }
+    let n = n as u64;
+    let (mut d, mut s) = (n - 1, 0);
+    while d % 2 == 0 {
+        d /= 2;
+        s += 1;
+    }
+    'outer: for &a in BASES.iter() {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'outer;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// 不超过n的最大整数k，满足k^e <= n
+fn root(n: i64, e: u32) -> i64 {
+    if n < 1 {
+        return 0;
+    }
+    let mut k = (n as f64).powf(1.0 / e as f64) as i64;
+    while k > 0 && (k as i128).pow(e) > n as i128 {
+        k -= 1;
+    }
+    while ((k + 1) as i128).pow(e) <= n as i128 {
+        k += 1;
+    }
+    k
+}
+
+/// 在[l, r]中寻找满足f的数，先随机尝试，都不满足时从随机位置开始依次检查，范围过大时放弃
+fn find(l: i64, r: i64, f: impl Fn(i64) -> bool) -> Option<i64> {
+    if l > r {
+        return None;
+    }
+    for _ in 0..10000 {
+        let x = random_range!(l, r);
+        if f(x) {
+            return Some(x);
+        }
+    }
+    let len = r as i128 - l as i128 + 1;
+    if len > 10_000_000 {
+        return None;
+    }
+    let start = random_range!(0, len as i64 - 1);
+    (0..len as i64)
+        .map(|k| l + (start + k) % len as i64)
+        .find(|&x| f(x))
+}
+
+fn choose(l: i64, r: i64, what: &str, f: impl Fn(i64) -> bool) -> i64 {
+    find(l, r, f).unwrap_or_else(|| {
+        error_info(&format!(
+            "Failed to find a {} between {} and {}",
+            what, l, r
+        ))
+    })
+}
+
+/// 较小的质因子p在[√(l/4), √r]中随机选择，再选择质数q >= p使p*q落在[l, r]内
+/// 区间很窄时q的范围(r - l) / p往往为空，此时直接在[l, r]中寻找
+fn semiprime(l: i64, r: i64) -> i64 {
+    for _ in 0..100 {
+        let p = match find(root(l / 4, 2).max(2), root(r, 2), is_prime) {
+            Some(p) => p,
+            None => break,
+        };
+        let lo = p.max(l / p + (l % p > 0) as i64); // 向上取整，l接近i64::MAX时l + p - 1会溢出
+        if let Some(q) = find(lo, r / p, is_prime) {
+            return p * q;
+        }
+    }
+    find(l, r, is_semiprime).unwrap_or_else(|| {
+        error_info(&format!(
+            "Failed to find a semiprime between {} and {}",
+            l, r
+        ))
+    })
+}
+
+/// x是否恰为两个质数之积。试除到x的立方根，没有找到因子的合数至多有两个质因子
+fn is_semiprime(x: i64) -> bool {
+    if x < 4 || is_prime(x) {
+        return false;
+    }
+    let limit = root(x, 3);
+    let mut p = 2;
+    while p <= limit {
+        if x % p == 0 {
+            return is_prime(x / p);
+        }
+        p += 1;
+    }
+    true
+}
+
+/// 不超过n的数中约数最多的（约数个数相同时取最小的）
+/// 这样的数的质因子是从2开始连续的质数，且指数不增，用DFS枚举
+fn highly_composite(n: i64) -> i64 {
+    fn dfs(i: usize, limit: u32, cur: i64, divisors: u64, n: i64, best: &mut (u64, i64)) {
+        if divisors > best.0 || divisors == best.0 && cur < best.1 {
+            *best = (divisors, cur);
+        }
+        if i == PRIMES.len() {
+            return;
+        }
+        let mut x = cur;
+        for e in 1..=limit {
+            x = match x.checked_mul(PRIMES[i]) {
+                Some(x) if x <= n => x,
+                _ => break,
+            };
+            dfs(i + 1, e, x, divisors * (e as u64 + 1), n, best);
+        }
+    }
+    if n < 1 {
+        error_info(&format!(
+            "There is no positive integer not greater than {}",
+            n
+        ));
+    }
+    let mut best = (1, 1);
+    dfs(0, 63, 1, 1, n, &mut best);
+    best.1
+}
+
+/// Chernick形式的Carmichael数(6k+1)(12k+1)(18k+1)，三个因子都是质数时成立
+fn carmichael(l: i64, r: i64) -> i64 {
+    let value = |k: i64| (6 * k as i128 + 1) * (12 * k as i128 + 1) * (18 * k as i128 + 1);
+    let (mut lo, mut hi) = (root(l / 1296, 3).max(1), root(r / 1296, 3) + 1);
+    while value(lo) < l as i128 {
+        lo += 1;
+    }
+    while lo > 1 && value(lo - 1) >= l as i128 {
+        lo -= 1;
+    }
+    while hi >= lo && value(hi) > r as i128 {
+        hi -= 1;
+    }
+    let k = find(lo, hi, |k| {
+        is_prime(6 * k + 1) && is_prime(12 * k + 1) && is_prime(18 * k + 1)
+    });
+    match k {
+        Some(k) => value(k) as i64,
+        None => error_info(&format!(
+            "Failed to find a Carmichael number between {} and {}",
+            l, r
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_prime() {
+        const N: usize = 1_000_000;
+        let mut sieve = vec![true; N + 1];
+        sieve[0] = false;
+        sieve[1] = false;
+        for i in 2..=N {
+            if sieve[i] {
+                for j in (i * i..=N).step_by(i) {
+                    sieve[j] = false;
+                }
+            }
+        }
+        for (n, &p) in sieve.iter().enumerate() {
+            assert_eq!(is_prime(n as i64), p, "{}", n);
+        }
+        assert!(is_prime(1_000_000_007));
+        assert!(!is_prime(3_215_031_751)); // 以2、3、5、7为底的强伪质数
+    }
+
+    /// Korselt判别法：n是无平方因子的合数，且对n的每个质因子p都有(p-1) | (n-1)
+    fn korselt(n: i64) -> bool {
+        let (mut m, mut factors) = (n, Vec::new());
+        let mut p = 2;
+        while p * p <= m {
+            if m % p == 0 {
+                m /= p;
+                if m % p == 0 {
+                    return false;
+                }
+                factors.push(p);
+            }
+            p += 1;
+        }
+        if m > 1 {
+            factors.push(m);
+        }
+        factors.len() >= 2 && factors.iter().all(|p| (n - 1) % (p - 1) == 0)
+    }
+
+    #[test]
+    fn test_is_semiprime() {
+        let count = |mut x: i64| {
+            let (mut k, mut p) = (0, 2);
+            while p * p <= x {
+                while x % p == 0 {
+                    x /= p;
+                    k += 1;
+                }
+                p += 1;
+            }
+            k + (x > 1) as i32
+        };
+        for x in 0..10000 {
+            assert_eq!(is_semiprime(x), count(x) == 2, "{}", x);
+        }
+        assert!(is_semiprime(1_000_000_000_000_000_007)); // 1370531 * 729644203597
+        assert!(!is_semiprime(1_000_000_000_000_000_001)); // 101 * 9901 * 999999000001
+    }
+
+    #[test]
+    fn test_carmichael() {
+        assert!(korselt(561) && korselt(1729) && !korselt(1105 * 5));
+        for &(l, r) in [
+            (1, 2000),
+            (1, 1_000_000_000),
+            (1_000_000, 1_000_000_000_000),
+        ]
+        .iter()
+        {
+            let n = carmichael(l, r);
+            assert!(l <= n && n <= r);
+            assert!(korselt(n), "{}", n);
+        }
+    }
+}
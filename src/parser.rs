@@ -1,27 +1,56 @@
-// FIXIT: 换行符被解析成除号
 // FIXIT: 有时明明有语法错误，却被忽略而非报错
 use crate::{
-    details::warning_info,
+    details::{error_info, warning_info},
+    include, macros, manifest, regex,
+    subtask::Mark,
     token::{Parameter::*, RandomString::*, Token::*, *},
 };
 use simple_combinators::{
-    combinator::{attempt, many1, optional, preview, satisfy},
+    combinator::{attempt, many, many1, optional, preview, satisfy},
     parser::*,
-    ParseError, Parser,
+    slice_some, ParseError, Parser,
 };
-use std::{collections::HashMap, ops::Range};
+use std::{cell::RefCell, collections::HashMap, ops::Range};
 
-static REGISTER: &[&str] = &["prefix", "pause", "folder", "std"];
+thread_local! {
+    // 解析中产生的警告及其在模板中的位置，回溯会重复解析同一处，因此按位置去重后在解析成功时输出
+    static WARNINGS: RefCell<Vec<(usize, String)>> = RefCell::default();
+}
+
+/// 记录`buf`开头处的警告，同一位置只记录一次
+fn warn_at(buf: &str, info: String) {
+    let key = (buf.as_ptr() as usize, info);
+    WARNINGS.with(|w| {
+        let mut w = w.borrow_mut();
+        if !w.contains(&key) {
+            w.push(key);
+        }
+    });
+}
+
+/// 输出并清空记录的警告
+pub fn flush_warnings() {
+    for (_, info) in WARNINGS.with(|w| w.replace(Vec::new())) {
+        warning_info(&info);
+    }
+}
+
+static REGISTER: &[&str] = &[
+    "prefix", "pause", "folder", "std", "seed", "export", "name", "outname", "format",
+];
 #[derive(Copy, Clone)]
 struct ConfigParser;
 impl Parser for ConfigParser {
     type ParseResult = Config;
     fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
         let mut map = HashMap::new();
-        let config_item = spaces()
-            .skip(char('#'))
-            .with(word())
-            .and(optional(parameters()));
+        let config_item = attempt(
+            spaces()
+                .skip(char('#'))
+                .with(word())
+                .flat_map(|k| if k == "include" { None } else { Some(k) }) // #include留给模板部分解析
+                .and(optional(parameters())),
+        );
         for (k, op) in config_item.iter(buf) {
             if !REGISTER.contains(&k.as_str()) {
                 warning_info(&format!("unsupported config: {}", k));
@@ -43,6 +72,49 @@ pub fn config() -> impl Parser<ParseResult = Config> {
     ConfigParser
 }
 
+/// 不求值地扫描配置，得到每一项的名字和方括号中的源码
+/// 用于在解析配置之前取出#seed，以及计算清单中与版本无关的哈希
+pub fn raw_config(mut buf: &str) -> Vec<(String, String)> {
+    let mut items = Vec::new();
+    let key = || attempt(spaces().skip(char('#')).with(word()));
+    while let Ok(k) = key().parse(&mut buf) {
+        if k == "include" {
+            break;
+        }
+        spaces().parse(&mut buf).ok();
+        let v = take_bracket(&mut buf).unwrap_or("");
+        items.push((k, v.to_string()));
+    }
+    items
+}
+
+/// 截取buf开头方括号中的内容，引号中的括号不计
+fn take_bracket<'a>(buf: &mut &'a str) -> Option<&'a str> {
+    if !buf.starts_with('[') {
+        return None;
+    }
+    let (mut depth, mut quote, mut prev) = (0, None, ' ');
+    for (i, c) in buf.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q && prev != '\\' => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    let inner = &buf[1..i];
+                    *buf = &buf[i + 1..];
+                    return Some(inner);
+                }
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
 pub fn file_range() -> impl Parser<ParseResult = Range<usize>> {
     spaces()
         .skip(string(":>"))
@@ -56,6 +128,165 @@ pub fn file_range() -> impl Parser<ParseResult = Range<usize>> {
         })
 }
 
+/// 解析由字母、数字和下划线组成的名字
+fn identifier() -> impl Parser<ParseResult = String> {
+    many1(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+fn variable_token() -> impl Parser<ParseResult = Token> {
+    char('$').with(identifier()).map(Variable)
+}
+
+pub fn subtask_mark() -> impl Parser<ParseResult = Mark> {
+    let depends = many(char(',').skip(spaces()).with(identifier()).skip(spaces()));
+    spaces()
+        .skip(char('@'))
+        .with(identifier())
+        .and(optional(
+            number()
+                .skip(spaces())
+                .and(depends)
+                .between(char('[').skip(spaces()), char(']')),
+        ))
+        .map(|(name, op)| match op {
+            Some((score, depends)) => Mark {
+                name,
+                score: Some(score),
+                depends,
+            },
+            None => Mark {
+                name,
+                score: None,
+                depends: Vec::new(),
+            },
+        })
+}
+
+#[derive(Copy, Clone)]
+struct DefinitionParser;
+impl Parser for DefinitionParser {
+    type ParseResult = Token;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        let params = identifier()
+            .sep_by(spaces().with(char(',')).skip(spaces()))
+            .or(spaces().map(|_| Vec::new()))
+            .between(char('(').skip(spaces()), spaces().with(char(')')));
+        let (name, params) = string("def")
+            .skip(space())
+            .skip(spaces())
+            .with(identifier())
+            .skip(spaces())
+            .and(params)
+            .skip(spaces())
+            .skip(char('='))
+            .skip(many::<_, String>(satisfy(|c: char| c == ' ' || c == '\t')))
+            .parse(buf)?;
+        let body = macros::take_body(buf).to_string();
+        macros::define(name, params, body);
+        Ok(TokenGroup(Vec::new())) // 定义本身不生成任何内容
+    }
+}
+pub fn definition() -> impl Parser<ParseResult = Token> {
+    DefinitionParser
+}
+
+#[derive(Copy, Clone)]
+struct IncludeParser {
+    top_level: bool, // 顶层的#include可以引用宏定义，写在生成器中的只能引用模板片段
+}
+impl Parser for IncludeParser {
+    type ParseResult = Token;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        let path = string("#include")
+            .skip(spaces())
+            .with(parameters())
+            .flat_map(|v| match &v[..] {
+                [Str(StrParameter::Confirm(s))] => Some(s.clone()),
+                _ => None,
+            })
+            .parse(buf)?;
+        let path = include::resolve(&path);
+        include::enter(&path);
+        let content = include::read(&path);
+        let mut body = content.as_str();
+        let tokens: Vec<Token> = if self.top_level {
+            attempt(statement()).iter(&mut body).collect()
+        } else {
+            attempt(token()).iter(&mut body).collect()
+        };
+        spaces().parse(&mut body).ok();
+        if !body.is_empty() {
+            let (line, column) = include::position(&content, body);
+            error_info(&format!(
+                "Something went wrong while parsing \"{}\" in {}:{}:{}",
+                slice_some(body),
+                path.display(),
+                line,
+                column
+            ));
+        }
+        include::leave();
+        Ok(TokenGroup(tokens))
+    }
+}
+pub fn include_token() -> impl Parser<ParseResult = Token> {
+    IncludeParser { top_level: true }
+}
+
+/// 模板顶层的一项：宏定义、#include或生成器
+/// 宏定义只能写在顶层，这里解析成功后不会再回溯，定义不会因为外层的解析失败而残留
+pub fn statement() -> impl Parser<ParseResult = Token> {
+    attempt(spaces().with(definition()))
+        .or(attempt(spaces().with(include_token())))
+        .or(token())
+}
+
+#[derive(Copy, Clone)]
+struct MacroCallParser;
+impl Parser for MacroCallParser {
+    type ParseResult = Token;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        let err = ParseError {
+            position: slice_some(buf),
+        };
+        let name = identifier()
+            .flat_map(|name| macros::is_defined(&name).then_some(name))
+            .skip(spaces())
+            .parse(buf)?;
+        let args = macros::split_args(buf).ok_or(err)?;
+        if let Some(tokens) = macros::cached(&name, &args) {
+            return Ok(TokenGroup(tokens));
+        }
+        let expanded = macros::expand(&name, &args);
+        macros::enter(&name);
+        let evaluated = manifest::evaluated();
+        let mut body = expanded.as_str();
+        // 宏体中#include的相对路径在定义宏的文件中解析
+        let tokens: Vec<Token> = include::within(macros::file(&name), || {
+            attempt(token()).iter(&mut body).collect()
+        });
+        spaces().parse(&mut body).ok();
+        if !body.is_empty() {
+            let file = macros::file(&name)
+                .map_or(String::new(), |f| format!(" (defined in {})", f.display()));
+            error_info(&format!(
+                "Something went wrong while expanding macro '{}'{} at \"{}\"",
+                name,
+                file,
+                slice_some(body)
+            ));
+        }
+        macros::leave();
+        if manifest::evaluated() == evaluated {
+            macros::cache(&name, &args, &tokens); // 含有`!`参数的宏每次调用都重新求值
+        }
+        Ok(TokenGroup(tokens))
+    }
+}
+fn macro_call() -> impl Parser<ParseResult = Token> {
+    MacroCallParser
+}
+
 #[derive(Copy, Clone)]
 struct TokenUnit;
 impl Parser for TokenUnit {
@@ -63,17 +294,31 @@ impl Parser for TokenUnit {
     fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
         spaces()
             .with(
-                attempt(constant())
+                attempt(macro_call())
+                    .or(attempt(IncludeParser { top_level: false }))
+                    .or(attempt(constant()))
+                    .or(variable_token())
+                    .or(attempt(if_token()))
+                    .or(attempt(function_token()))
+                    .or(attempt(pick_token()))
+                    .or(attempt(sample_token()))
+                    .or(attempt(number_token()))
+                    .or(attempt(interval_token()))
+                    .or(attempt(grid_token()))
+                    .or(attempt(maze_token()))
+                    .or(attempt(geometry_token()))
+                    .or(attempt(neg_token()))
                     .or(attempt(integer_pair_token()))
                     .or(attempt(random_integer_token()))
                     .or(random_string_token())
                     .or(repeated_token())
                     .or(array_token())
                     .or(distribute_token())
+                    .or(query_token())
                     .or(token_group()),
             )
             .skip(spaces())
-            .or(token().between(char('('), char(')')))
+            .or(expression().between(char('('), char(')')))
             .parse(buf)
     }
 }
@@ -81,136 +326,276 @@ fn token_unit() -> impl Parser<ParseResult = Token> {
     TokenUnit
 }
 
-#[derive(Copy, Clone)]
-struct TokenWithMulAndDiv;
-impl Parser for TokenWithMulAndDiv {
-    type ParseResult = Token;
-    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
-        let tail = || {
-            optional(
-                spaces()
-                    .with(char('*').or(char('/')))
-                    .skip(spaces())
-                    .and(token_unit()),
-            )
-        };
-        let mut cur = token_unit().parse(buf)?;
-        for res in tail().iter(buf) {
-            match res {
-                Some(('*', r)) => {
-                    cur = ProdToken(Box::new(cur), Box::new(r));
-                }
-                Some(('/', r)) => {
-                    cur = QuotToken(Box::new(cur), Box::new(r));
-                }
-                _ => {
-                    break;
+/// 一元负号，如`-$i`、`-(i10*2)`
+fn neg_token() -> impl Parser<ParseResult = Token> {
+    char('-')
+        .skip(spaces())
+        .with(token_unit())
+        .flat_map(|t| t.is_int_token().then_some(NegToken(Box::new(t))))
+}
+
+/// 内置函数`min(...)`、`max(...)`、`abs(x)`和`pow(a, b)`，参数都是整数生成器
+fn function_token() -> impl Parser<ParseResult = Token> {
+    let args = expression()
+        .sep_by(spaces().with(char(',')).skip(spaces()))
+        .between(char('(').skip(spaces()), spaces().with(char(')')));
+    identifier()
+        .skip(spaces())
+        .and(args)
+        .flat_map(|(name, mut args): (String, Vec<Token>)| {
+            if !args.iter().all(|t| t.is_int_token()) {
+                return None;
+            }
+            match (name.as_str(), args.len()) {
+                ("min", n) if n > 0 => Some(MinToken(args)),
+                ("max", n) if n > 0 => Some(MaxToken(args)),
+                ("abs", 1) => Some(AbsToken(Box::new(args.remove(0)))),
+                ("pow", 2) => {
+                    let b = args.remove(1);
+                    Some(PowToken(Box::new(args.remove(0)), Box::new(b)))
                 }
+                _ => None,
             }
-        }
-        Ok(cur)
-    }
+        })
 }
-fn token_with_mul_and_div() -> impl Parser<ParseResult = Token> {
-    TokenWithMulAndDiv
+
+/// 解析表达式时的上下文
+#[derive(Copy, Clone)]
+struct Context {
+    division: bool, // 是否把`/`解析为除号
+}
+
+type Binary = fn(Box<Token>, Box<Token>) -> Token;
+
+/// 二元运算符，按优先级从低到高分层，同一层的运算符左结合
+static OPERATORS: &[&[(&str, Binary)]] = &[
+    &[("|", BitOrToken)],
+    &[("^", BitXorToken)],
+    &[("&", BitAndToken)],
+    &[("<<", ShlToken), (">>", ShrToken)],
+    &[("+", SumToken), ("-", DifToken)],
+    &[
+        ("*", ProdToken),
+        ("%", ModToken),
+        ("/", QuotToken),
+        ("div", QuotToken),
+    ],
+];
+
+/// 匹配buf开头属于第level层的运算符
+fn operator(level: usize, context: Context, buf: &str) -> Option<(&'static str, Binary)> {
+    OPERATORS[level].iter().copied().find(|&(op, _)| {
+        buf.starts_with(op)
+            && match op {
+                "/" => context.division,
+                "div" => !buf[3..].starts_with(|c: char| c.is_ascii_alphanumeric()),
+                _ => true,
+            }
+    })
 }
 
+/// 按OPERATORS逐层解析二元运算，超过最后一层时解析单个生成器
 #[derive(Copy, Clone)]
-struct TokenWithAddAndSub;
-impl Parser for TokenWithAddAndSub {
+struct BinaryParser {
+    level: usize,
+    context: Context,
+}
+impl Parser for BinaryParser {
     type ParseResult = Token;
     fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
-        let tail = || {
-            optional(
-                spaces()
-                    .with(char('+').or(char('-')))
-                    .skip(spaces())
-                    .and(token_with_mul_and_div()),
-            )
+        if self.level == OPERATORS.len() {
+            let start = *buf;
+            let unit = token_unit().parse(buf)?;
+            if !self.context.division {
+                warn_ambiguous_division(&unit, &start[..start.len() - buf.len()], buf);
+            }
+            return Ok(unit);
+        }
+        let next = BinaryParser {
+            level: self.level + 1,
+            ..*self
         };
-        let mut cur = token_with_mul_and_div().parse(buf)?;
-        for res in tail().iter(buf) {
-            match res {
-                Some(('+', r)) => {
-                    cur = SumToken(Box::new(cur), Box::new(r));
-                }
-                Some(('-', r)) => {
-                    cur = DifToken(Box::new(cur), Box::new(r));
-                }
-                _ => {
-                    break;
+        let mut cur = next.parse(buf)?;
+        loop {
+            let mut rest = *buf;
+            spaces().parse(&mut rest)?;
+            let (op, make) = match operator(self.level, self.context, rest) {
+                Some(op) => op,
+                None => break,
+            };
+            rest = &rest[op.len()..];
+            spaces().parse(&mut rest)?;
+            match next.parse(&mut rest) {
+                Ok(r) => {
+                    cur = make(Box::new(cur), Box::new(r));
+                    *buf = rest;
                 }
+                Err(_) => break, // 运算符后面不是生成器时，运算符留给后续解析
             }
         }
         Ok(cur)
     }
 }
-pub fn token_with_add_and_sub() -> impl Parser<ParseResult = Token> {
-    TokenWithAddAndSub
+fn binary(context: Context) -> impl Parser<ParseResult = Token> {
+    BinaryParser { level: 0, context }
+}
+
+/// 以前`G1 / G2`被解析为除法，现在解析为换行
+/// 对`/`紧贴在两个整数之间的写法（如`$n/2`）给出警告，两侧有空白时视为有意的换行
+fn warn_ambiguous_division(cur: &Token, text: &str, buf: &str) {
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    if !cur.is_int_token() || text.ends_with(is_blank) || !buf.starts_with('/') {
+        return;
+    }
+    let mut next = &buf[1..];
+    if next.starts_with(|c: char| is_blank(c) || c == '/') {
+        return;
+    }
+    let is_int = attempt(random_integer_token())
+        .or(variable_token())
+        .or(number().map(ConstantInteger))
+        .parse(&mut next)
+        .is_ok();
+    if is_int {
+        let info = format!(
+            "'/' in \"{}\" is parsed as a newline, write (G1 / G2) or G1 div G2 for division",
+            slice_some(buf)
+        );
+        warn_at(buf, info);
+    }
 }
+
+/// 运算符的优先级从低到高依次为`|`、`^`、`&`、`<< >>`、`+ -`、`* / %`、一元负号
+/// 在模板中`/`表示换行，只有在圆括号、方括号等内部才表示除号，其余地方除法需要写成`div`
 pub fn token() -> impl Parser<ParseResult = Token> {
-    token_with_add_and_sub()
+    attempt(spaces().with(char('/')).skip(spaces()).map(|_| NewLine))
+        .or(binary(Context { division: false }))
+}
+
+/// 可以用`/`表示除号的表达式
+fn expression() -> impl Parser<ParseResult = Token> {
+    binary(Context { division: true })
 }
 
 pub fn integer_pair_token() -> impl Parser<ParseResult = Token> {
     random_integer()
-        .and(
-            spaces()
-                .with(
-                    string("<=")
-                        .or(string(">="))
-                        .or(string("<"))
-                        .or(string(">")),
-                )
-                .skip(spaces()),
-        )
+        .and(spaces().with(cmp_op()).skip(spaces()))
         .and(random_integer())
-        .flat_map(|tuple| match tuple {
-            ((t1, op), t2) => Some(RandomIntegerPair(
-                t1.left(),
-                t1.right(),
-                t2.left(),
-                t2.right(),
-                match_op(op)?,
-            )),
+        .map(|((t1, op), t2)| {
+            use crate::token::RandomInteger::Shaped;
+            if let (Shaped(..), _) | (_, Shaped(..)) = (&t1, &t2) {
+                error_info("Integer pairs do not support distributions other than uniform");
+            }
+            RandomIntegerPair(t1.left(), t1.right(), t2.left(), t2.right(), op)
         })
 }
 
+fn cmp_op() -> impl Parser<ParseResult = Cmp> {
+    string("<=")
+        .or(string(">="))
+        .or(string("=="))
+        .or(string("!="))
+        .or(string("<"))
+        .or(string(">"))
+        .flat_map(match_op)
+}
+
 fn match_op(op: &str) -> Option<Cmp> {
     match op {
         "<" => Some(Cmp::LessThan),
         ">" => Some(Cmp::GreaterThan),
         "<=" => Some(Cmp::NoGreaterThan),
         ">=" => Some(Cmp::NoLessThan),
+        "==" => Some(Cmp::Equal),
+        "!=" => Some(Cmp::NotEqual),
         _ => None,
     }
 }
 
-fn normal_parameter() -> impl Parser<ParseResult = Parameter> {
+#[derive(Copy, Clone)]
+struct IfTokenParser;
+impl Parser for IfTokenParser {
+    type ParseResult = Token;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        // 先把左侧作为单个随机整数解析，避免`i10 < i20`被当作整数对
+        let condition = attempt(
+            random_integer_token()
+                .skip(spaces())
+                .and(cmp_op().skip(spaces()))
+                .and(expression())
+                .between(char('[').skip(spaces()), spaces().with(char(']'))),
+        )
+        .or(expression()
+            .and(cmp_op().skip(spaces()))
+            .and(expression())
+            .between(char('[').skip(spaces()), spaces().with(char(']'))));
+        let else_branch = optional(attempt(
+            spaces().with(string("else")).skip(spaces()).with(token()),
+        ));
+        string("if")
+            .skip(spaces())
+            .with(condition)
+            .flat_map(|((l, op), r)| {
+                if l.is_int_token() && r.is_int_token() {
+                    Some((l, op, r))
+                } else {
+                    None
+                }
+            })
+            .and(token())
+            .and(else_branch)
+            .map(|(((l, op, r), then), otherwise)| {
+                If(
+                    Box::new(l),
+                    op,
+                    Box::new(r),
+                    Box::new(then),
+                    otherwise.map(Box::new),
+                )
+            })
+            .parse(buf)
+    }
+}
+fn if_token() -> impl Parser<ParseResult = Token> {
+    IfTokenParser
+}
+
+/// 方括号中的整数后面还有运算时整体作为表达式解析，如`1e9+7`
+/// 不带方括号的参数不作表达式解析，因此`i10*100`仍表示`(i10)*100`
+fn normal_parameter(bracketed: bool) -> impl Parser<ParseResult = Parameter> {
     attempt(quoted_string())
         .map(|s| Str(StrParameter::Confirm(s)))
-        .or(number().map(|i| Int(IntParameter::Confirm(i))))
+        .or(number()
+            .skip(preview(spaces().with(satisfy(move |c: char| {
+                !bracketed || !"+-*/%&|^<>".contains(c)
+            }))))
+            .map(|i| Int(IntParameter::Confirm(i))))
 }
 
 fn exclmark_parameter() -> impl Parser<ParseResult = Parameter> {
-    char('!').with(
-        random_string_token()
-            .flat_map(|token| token.generate())
-            .or(token().flat_map(|token| {
-                if token.is_int_token() {
-                    token.generate()
-                } else {
-                    None
-                }
-            })),
-    )
+    char('!')
+        .with(
+            random_string_token()
+                .flat_map(|token| token.value())
+                .or(expression().flat_map(|token| {
+                    if token.is_int_token() {
+                        token.value()
+                    } else {
+                        None
+                    }
+                })),
+        )
+        .map(|p| {
+            manifest::record(&p); // 每次运行的值可能不同，计入清单的哈希
+            p
+        })
 }
 
 fn quesmark_parameter() -> impl Parser<ParseResult = Parameter> {
     char('?').with(
         random_string_token()
             .map(|token| (Str(StrParameter::Lazy(Box::new(token)))))
-            .or(token().flat_map(|token| {
+            .or(expression().flat_map(|token| {
                 if token.is_int_token() {
                     Some(Int(IntParameter::Lazy(Box::new(token))))
                 } else {
@@ -227,6 +612,18 @@ fn random_integer() -> impl Parser<ParseResult = crate::token::RandomInteger> {
         .flat_map(|v| match &v[..] {
             [Int(a)] => Some(NoGreaterThan(a.clone())),
             [Int(a), Int(b)] => Some(Between(a.clone(), b.clone())),
+            [Int(a), Int(b), Enum(e)] => {
+                let shape = match e.as_str() {
+                    "extreme" => Shape::Extreme,
+                    "log" => Shape::Log,
+                    "normal" => Shape::Normal,
+                    _ => return None,
+                };
+                Some(Shaped(a.clone(), b.clone(), shape))
+            }
+            [Int(a), Int(b), Enum(e), Int(p)] if e == "max" => {
+                Some(Shaped(a.clone(), b.clone(), Shape::Max(p.clone())))
+            }
             _ => None,
         })
 }
@@ -256,55 +653,127 @@ fn random_string_token() -> impl Parser<ParseResult = Token> {
             [Enum(e), Char(l), Char(r), Int(t)] if e == "between" => {
                 Some(RandomString(Between(*l, *r, t.clone())))
             }
+            [Enum(e), Str(StrParameter::Confirm(s)), Int(t)] if e == "weighted" => {
+                let weights = char_weights(s).unwrap_or_else(|| {
+                    error_info(&format!("Invalid character weights: \"{}\"", s))
+                });
+                Some(RandomString(Weighted(weights, t.clone())))
+            }
+            [Enum(e), Int(t)] if e == "palindrome" => {
+                Some(RandomString(Palindrome(lower(), t.clone())))
+            }
+            [Enum(e), Str(s), Int(t)] if e == "palindrome" => {
+                Some(RandomString(Palindrome(s.clone(), t.clone())))
+            }
+            [Enum(e), Int(t)] if e == "brackets" => Some(RandomString(Brackets(
+                StrParameter::Confirm(String::from("()")),
+                t.clone(),
+            ))),
+            [Enum(e), Str(s), Int(t)] if e == "brackets" => {
+                Some(RandomString(Brackets(s.clone(), t.clone())))
+            }
+            [Enum(e), Int(p), Int(t)] if e == "periodic" => {
+                Some(RandomString(Periodic(lower(), p.clone(), t.clone())))
+            }
+            [Enum(e), Str(s), Int(p), Int(t)] if e == "periodic" => {
+                Some(RandomString(Periodic(s.clone(), p.clone(), t.clone())))
+            }
+            [Enum(e), Int(t)] if e == "fibonacci" => Some(RandomString(Fibonacci(ab(), t.clone()))),
+            [Enum(e), Str(s), Int(t)] if e == "fibonacci" => {
+                Some(RandomString(Fibonacci(s.clone(), t.clone())))
+            }
+            [Enum(e), Int(t)] if e == "thuemorse" => Some(RandomString(ThueMorse(ab(), t.clone()))),
+            [Enum(e), Str(s), Int(t)] if e == "thuemorse" => {
+                Some(RandomString(ThueMorse(s.clone(), t.clone())))
+            }
+            [Enum(e), Int(b), Int(m)] if e == "antihash" => {
+                Some(RandomString(AntiHash(b.clone(), Some(m.clone()))))
+            }
+            [Enum(e), Int(b), Enum(m)] if e == "antihash" && m == "overflow" => {
+                Some(RandomString(AntiHash(b.clone(), None)))
+            }
+            [Enum(e), Str(StrParameter::Confirm(s))] if e == "regex" => {
+                Some(RandomString(Regex(regex::parse(s).unwrap_or_else(|| {
+                    error_info(&format!("Invalid regex: \"{}\"", s))
+                }))))
+            }
             _ => None,
         })
 }
 
+/// 解析形如`a:10 b:1 c:0.5`的字符权重表，权重不能为负，也不能全为0
+fn char_weights(s: &str) -> Option<Vec<(char, f64)>> {
+    let mut buf = s;
+    let v: Vec<(char, f64)> = spaces()
+        .with(many1(any().skip(char(':')).and(float()).skip(spaces())))
+        .parse(&mut buf)
+        .ok()?;
+    let valid = v.iter().all(|(_, w)| *w >= 0.0) && v.iter().any(|(_, w)| *w > 0.0);
+    (buf.is_empty() && valid).then_some(v)
+}
+
+fn lower() -> StrParameter {
+    StrParameter::Confirm(('a'..='z').collect())
+}
+
+fn ab() -> StrParameter {
+    StrParameter::Confirm(String::from("ab"))
+}
+
 pub fn constant() -> impl Parser<ParseResult = Token> {
     quoted_string()
         .map(|s| ConstantString(s))
         .or(number().map(|i| ConstantInteger(i)))
-        .or(char('/').map(|_| NewLine))
 }
 
 #[derive(Copy, Clone)]
-struct ParameterParser;
+struct ParameterParser {
+    bracketed: bool,
+}
 impl Parser for ParameterParser {
     type ParseResult = Parameter;
     fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
-        attempt(normal_parameter())
-            .or(exclmark_parameter())
-            .or(quesmark_parameter())
-            .or(attempt(
-                any().between(char('\''), char('\'')).map(|c| Char(c)),
-            ))
-            .or(attempt(
-                string("true")
-                    .skip(preview(satisfy(|c: char| !c.is_alphabetic())))
-                    .map(|_| Bool(true)),
-            ))
-            .or(attempt(
-                string("false")
-                    .skip(preview(satisfy(|c: char| !c.is_alphabetic())))
-                    .map(|_| Bool(false)),
-            ))
-            .or(attempt(token()).flat_map(|t| match t.generate()? {
-                p @ Int(_) => Some(p),
-                _ => None,
-            }))
-            .or(word().map(|e| Enum(e)))
-            .parse(buf)
+        attempt(
+            word()
+                .skip(spaces())
+                .skip(char('='))
+                .skip(spaces())
+                .and(parameter())
+                .map(|(k, v)| Named(k, Box::new(v))),
+        )
+        .or(attempt(normal_parameter(self.bracketed)))
+        .or(exclmark_parameter())
+        .or(quesmark_parameter())
+        .or(attempt(
+            any().between(char('\''), char('\'')).map(|c| Char(c)),
+        ))
+        .or(attempt(
+            string("true")
+                .skip(preview(satisfy(|c: char| !c.is_alphabetic())))
+                .map(|_| Bool(true)),
+        ))
+        .or(attempt(
+            string("false")
+                .skip(preview(satisfy(|c: char| !c.is_alphabetic())))
+                .map(|_| Bool(false)),
+        ))
+        .or(attempt(expression()).flat_map(|t| match t.value()? {
+            p @ Int(_) => Some(p),
+            _ => None,
+        }))
+        .or(word().map(|e| Enum(e)))
+        .parse(buf)
     }
 }
 fn parameter() -> ParameterParser {
-    ParameterParser
+    ParameterParser { bracketed: true }
 }
 
 fn parameters() -> impl Parser<ParseResult = Vec<Parameter>> {
     parameter()
         .sep_by(spaces().with(char(',')).skip(spaces()))
         .between(char('[').skip(spaces()), spaces().with(char(']')))
-        .or(parameter().sep_by(char(',')))
+        .or(ParameterParser { bracketed: false }.sep_by(char(',')))
 }
 
 #[derive(Copy, Clone)]
@@ -350,18 +819,304 @@ fn distribute_token() -> impl Parser<ParseResult = Token> {
 }
 
 #[derive(Copy, Clone)]
-struct RepeatedTokenParser;
-impl Parser for RepeatedTokenParser {
+struct QueryToken;
+impl Parser for QueryToken {
     type ParseResult = Token;
     fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
-        char('X')
+        let options = parameter()
+            .skip(spaces())
+            .skip(char(':'))
+            .and(token())
+            .flat_map(|(p, t)| match p {
+                Int(i) => Some((i, t)),
+                _ => None,
+            })
+            .sep_by(spaces().skip(char(';')).skip(spaces()))
+            .between(char('{').skip(spaces()), spaces().with(char('}')));
+        char('Q')
             .with(parameters())
             .flat_map(|v| match &v[..] {
-                [Int(ip)] => Some(ip.clone()),
+                [Int(q)] => Some(q.clone()),
                 _ => None,
             })
+            .skip(spaces())
+            .and(options)
+            .map(|(q, v)| Query(q, v))
+            .parse(buf)
+    }
+}
+/// q行询问，每行按权重选择一种，如`Q[q]{3: i10 i10; 1: interval[10]}`
+fn query_token() -> impl Parser<ParseResult = Token> {
+    QueryToken
+}
+
+/// 数论生成器，如`prime[l, r]`、`hcn[n]`，只有一个参数r时范围为[1, r]
+fn number_token() -> impl Parser<ParseResult = Token> {
+    identifier()
+        .and(parameters())
+        .flat_map(|(name, v): (String, Vec<Parameter>)| {
+            let kind = match name.as_str() {
+                "prime" => NumberKind::Prime,
+                "composite" => NumberKind::Composite,
+                "semiprime" => NumberKind::Semiprime,
+                "hcn" => NumberKind::HighlyComposite,
+                "carmichael" => NumberKind::Carmichael,
+                "pow2" => NumberKind::Pow2,
+                _ => return None,
+            };
+            match &v[..] {
+                [Int(r)] => Some(Number(kind, IntParameter::Confirm(1), r.clone())),
+                [Int(l), Int(r)] if kind != NumberKind::HighlyComposite => {
+                    Some(Number(kind, l.clone(), r.clone()))
+                }
+                _ => None,
+            }
+        })
+}
+
+/// 数组中的区间`l r`，如`interval[n]`、`interval[n, short]`
+fn interval_token() -> impl Parser<ParseResult = Token> {
+    string("interval")
+        .with(parameters())
+        .flat_map(|v| match &v[..] {
+            [Int(n)] => Some(Interval(n.clone(), IntervalKind::Uniform)),
+            [Int(n), Enum(e)] => {
+                let kind = match e.as_str() {
+                    "short" => IntervalKind::Short,
+                    "long" => IntervalKind::Long,
+                    "full" => IntervalKind::Full,
+                    _ => return None,
+                };
+                Some(Interval(n.clone(), kind))
+            }
+            _ => None,
+        })
+}
+
+/// 从若干生成器中随机选择一个，如`pick["a", 2.5: i10]`，没有写权重的选项权重为1
+fn pick_token() -> impl Parser<ParseResult = Token> {
+    let option = optional(attempt(
+        float().skip(spaces()).skip(char(':')).skip(spaces()),
+    ))
+    .and(expression())
+    .map(|(w, t)| (w.unwrap_or(1.0), t));
+    string("pick")
+        .skip(spaces())
+        .with(
+            option
+                .sep_by(spaces().with(char(',')).skip(spaces()))
+                .between(char('[').skip(spaces()), spaces().with(char(']'))),
+        )
+        .map(Pick)
+}
+
+/// 从若干生成器中不重复地选择k个，如`sample[2, "a", "b", i10]`
+fn sample_token() -> impl Parser<ParseResult = Token> {
+    let options = expression().sep_by(spaces().with(char(',')).skip(spaces()));
+    string("sample")
+        .skip(spaces())
+        .with(
+            parameter()
+                .skip(spaces().with(char(',')).skip(spaces()))
+                .and(options)
+                .between(char('[').skip(spaces()), spaces().with(char(']'))),
+        )
+        .flat_map(|(k, v)| match k {
+            Int(k) => Some(Sample(k, v)),
+            _ => None,
+        })
+}
+
+/// 重复次数以及可选的具名参数，如`[n, sep=",", var=k]`
+fn repeat_parameters(v: &[Parameter]) -> Option<(IntParameter, RepeatOption)> {
+    let (ip, named) = match v {
+        [Int(ip), named @ ..] => (ip.clone(), named),
+        _ => return None,
+    };
+    let mut option = RepeatOption::default();
+    for p in named.iter() {
+        match p {
+            Named(k, p) => match (k.as_str(), &**p) {
+                ("sep", Str(StrParameter::Confirm(s))) => option.sep = Some(s.clone()),
+                ("var", Enum(e)) => option.var = Some(e.clone()),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    Some((ip, option))
+}
+
+/// 网格的行数、列数以及可选的具名参数，如`[n, m, chars=".#", density=30, path=true]`
+fn grid_parameters(v: &[Parameter]) -> Option<(IntParameter, IntParameter, GridOption)> {
+    let (n, m, named) = match v {
+        [Int(n), Int(m), named @ ..] => (n.clone(), m.clone(), named),
+        _ => return None,
+    };
+    let mut option = GridOption {
+        chars: vec!['.', '#'],
+        density: None,
+        path: false,
+        from: [None, None],
+        to: [None, None],
+        sep: None,
+        value: None,
+    };
+    let mut is_char_grid = false;
+    for p in named.iter() {
+        match p {
+            Named(k, p) => match (k.as_str(), &**p) {
+                ("chars", Str(StrParameter::Confirm(s))) => {
+                    option.chars = s.chars().collect();
+                    is_char_grid = true;
+                }
+                ("density", Int(d)) => {
+                    option.density = Some(d.clone());
+                    is_char_grid = true;
+                }
+                ("path", Bool(b)) => {
+                    option.path = *b;
+                    is_char_grid = true;
+                }
+                // 指定路径的端点时自动启用path
+                (k, Int(i)) if ["sx", "sy", "tx", "ty"].contains(&k) => {
+                    let end = match k {
+                        "sx" => &mut option.from[0],
+                        "sy" => &mut option.from[1],
+                        "tx" => &mut option.to[0],
+                        _ => &mut option.to[1],
+                    };
+                    *end = Some(i.clone());
+                    option.path = true;
+                    is_char_grid = true;
+                }
+                ("sep", Str(StrParameter::Confirm(s))) => option.sep = Some(s.clone()),
+                ("value", Int(i)) => option.value = Some(i.clone()),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    if option.value.is_some() && is_char_grid {
+        error_info("The value of a grid cannot be combined with chars, density or path");
+    }
+    let need_obstacle = option.density.is_some() || option.path;
+    if option.chars.is_empty() || need_obstacle && option.chars.len() < 2 {
+        error_info(&format!(
+            "The chars of a grid should contain a free cell and at least one obstacle, but \"{}\" was given",
+            option.chars.iter().collect::<String>()
+        ));
+    }
+    Some((n, m, option))
+}
+
+fn grid_token() -> impl Parser<ParseResult = Token> {
+    string("grid")
+        .with(parameters())
+        .flat_map(|v| grid_parameters(&v))
+        .map(|(n, m, option)| Grid(n, m, option))
+}
+
+/// 迷宫的行数、列数以及可选的具名参数，如`[n, m, algo=prim, wall='#', extra=10]`
+fn maze_parameters(v: &[Parameter]) -> Option<(IntParameter, IntParameter, MazeOption)> {
+    let (n, m, named) = match v {
+        [Int(n), Int(m), named @ ..] => (n.clone(), m.clone(), named),
+        _ => return None,
+    };
+    let mut option = MazeOption {
+        algo: MazeAlgo::Dfs,
+        wall: '#',
+        path: '.',
+        start: None,
+        end: None,
+        extra: IntParameter::Confirm(0),
+        sep: None,
+    };
+    for p in named.iter() {
+        match p {
+            Named(k, p) => match (k.as_str(), &**p) {
+                ("algo", Enum(e)) => {
+                    option.algo = match e.as_str() {
+                        "dfs" => MazeAlgo::Dfs,
+                        "prim" => MazeAlgo::Prim,
+                        "kruskal" => MazeAlgo::Kruskal,
+                        _ => error_info(&format!("Unknown maze algorithm: {}", e)),
+                    }
+                }
+                ("wall", Char(c)) => option.wall = *c,
+                ("path", Char(c)) => option.path = *c,
+                ("start", Char(c)) => option.start = Some(*c),
+                ("end", Char(c)) => option.end = Some(*c),
+                ("extra", Int(i)) => option.extra = i.clone(),
+                ("sep", Str(StrParameter::Confirm(s))) => option.sep = Some(s.clone()),
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    Some((n, m, option))
+}
+
+fn maze_token() -> impl Parser<ParseResult = Token> {
+    string("maze")
+        .with(parameters())
+        .flat_map(|v| maze_parameters(&v))
+        .map(|(n, m, option)| Maze(n, m, option))
+}
+
+/// 几何生成器的种类、数目、坐标范围以及可选的具名参数，如`[convex, n, 0, 1e9, order=cw]`
+fn geometry_parameters(
+    v: &[Parameter],
+) -> Option<(GeoKind, IntParameter, IntParameter, IntParameter, GeoOption)> {
+    let (kind, n, l, r, named) = match v {
+        [Enum(e), Int(n), Int(l), Int(r), named @ ..] => {
+            (e, n.clone(), l.clone(), r.clone(), named)
+        }
+        _ => return None,
+    };
+    let kind = match kind.as_str() {
+        "points" => GeoKind::Points,
+        "distinct" => GeoKind::Distinct,
+        "general" => GeoKind::General,
+        "convex" => GeoKind::Convex,
+        "polygon" => GeoKind::Polygon,
+        "segments" => GeoKind::Segments,
+        _ => return None,
+    };
+    let mut option = GeoOption::default();
+    for p in named.iter() {
+        match p {
+            Named(k, p) => match (k.as_str(), &**p) {
+                ("order", Enum(e)) if e == "cw" => option.clockwise = true,
+                ("order", Enum(e)) if e == "ccw" => option.clockwise = false,
+                ("precision", Int(IntParameter::Confirm(d))) if (0..=18).contains(d) => {
+                    option.precision = *d as u32
+                }
+                _ => return None,
+            },
+            _ => return None,
+        }
+    }
+    Some((kind, n, l, r, option))
+}
+
+fn geometry_token() -> impl Parser<ParseResult = Token> {
+    string("geo")
+        .with(parameters())
+        .flat_map(|v| geometry_parameters(&v))
+        .map(|(kind, n, l, r, option)| Geometry(kind, n, l, r, option))
+}
+
+#[derive(Copy, Clone)]
+struct RepeatedTokenParser;
+impl Parser for RepeatedTokenParser {
+    type ParseResult = Token;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        char('X')
+            .with(parameters())
+            .flat_map(|v| repeat_parameters(&v))
             .and(token())
-            .map(|(ip, token)| Repeat(ip, Box::new(token)))
+            .map(|((ip, option), token)| Repeat(ip, option, Box::new(token)))
             .parse(buf)
     }
 }
@@ -376,15 +1131,105 @@ impl Parser for ArrayTokenParser {
     fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
         char('A')
             .with(parameters())
-            .flat_map(|v| match &v[..] {
-                [Int(ip)] => Some(ip.clone()),
-                _ => None,
-            })
+            .flat_map(|v| repeat_parameters(&v))
             .and(token())
-            .map(|(ip, token)| Array(ip, Box::new(token)))
+            .map(|((ip, option), token)| Array(ip, option, Box::new(token)))
             .parse(buf)
     }
 }
 fn array_token() -> impl Parser<ParseResult = Token> {
     ArrayTokenParser
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str) -> i64 {
+        let mut buf = s;
+        let t = expression().parse(&mut buf).ok().unwrap();
+        assert!(buf.is_empty(), "\"{}\" left after parsing \"{}\"", buf, s);
+        t.value().unwrap().int().unwrap()
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1+2*3"), 7);
+        assert_eq!(eval("1 | 6 ^ 3 & 5"), 7);
+        assert_eq!(eval("1 << 4 >> 2"), 4);
+        assert_eq!(eval("1 + 1 << 2"), 8);
+        assert_eq!(eval("7 - 2 - 1"), 4);
+        assert_eq!(eval("100 / 7 % 4"), 2);
+        assert_eq!(eval("100 div 7"), 14);
+        assert_eq!(eval("-(2 * 3) + pow(2, 10)"), 1018);
+        assert_eq!(eval("(1 | 2) * 3"), 9);
+    }
+
+    #[test]
+    fn test_if_condition() {
+        let conditions = [
+            "if[i[1,5] < i[6,9]] {1} else {2}",
+            "if[i[5,9] >= i[1,4] + 1] {1}",
+        ];
+        for s in conditions.iter() {
+            let mut buf = *s;
+            let t = if_token().parse(&mut buf).ok().unwrap();
+            assert!(buf.is_empty());
+            assert!(matches!(t, If(ref l, _, _, _, _) if matches!(**l, RandomInteger(_))));
+            assert_eq!(t.generate_str(" ").unwrap(), "1 ");
+        }
+        for s in ["if[i10 < i20] {1}", "if[$x + 1 == 2] {1}"].iter() {
+            let mut buf = *s;
+            assert!(if_token().parse(&mut buf).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_comment() {
+        let mut buf = "# header\ni10 # count\n/ /* block */ i10 # at the end";
+        let tokens: Vec<Token> = attempt(statement()).iter(&mut buf).collect();
+        assert!(matches!(
+            tokens[..],
+            [RandomInteger(_), NewLine, RandomInteger(_)]
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_newline() {
+        let mut buf = "i100 / i100";
+        let tokens: Vec<Token> = attempt(token()).iter(&mut buf).collect();
+        assert!(matches!(
+            tokens[..],
+            [RandomInteger(_), NewLine, RandomInteger(_)]
+        ));
+        // `i10*100`表示`(i10)*100`，而不是`i[1000]`
+        let mut buf = "i10*100";
+        let t = token().parse(&mut buf).ok().unwrap();
+        assert!(matches!(t, ProdToken(ref l, ref r)
+            if matches!(**l, RandomInteger(_)) && matches!(**r, ConstantInteger(100))));
+    }
+
+    #[test]
+    fn test_bracket_division() {
+        for s in ["i[1, 10/2]", "i[1, 10 / 2]", "i[1, 1<<2|1]"].iter() {
+            let mut buf = *s;
+            let t = token().parse(&mut buf).ok().unwrap();
+            assert!(buf.is_empty());
+            for _ in 0..100 {
+                let v = t.value().unwrap().int().unwrap();
+                assert!((1..=5).contains(&v), "{} generated {}", s, v);
+            }
+        }
+    }
+
+    #[test]
+    fn test_division_warning_once() {
+        let mut buf = "{$n/2} i10/i10 i10 / i10";
+        let tokens: Vec<Token> = attempt(statement()).iter(&mut buf).collect();
+        assert!(buf.is_empty());
+        assert_eq!(tokens.len(), 7);
+        let warnings = WARNINGS.with(|w| w.replace(Vec::new()));
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+    }
+}
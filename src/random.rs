@@ -1,9 +1,31 @@
 use crate::{
+    antihash::collision,
+    details::error_info,
     resolve,
     token::{RandomString, RandomString::*, *},
 };
 use num::cast::ToPrimitive;
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::{index, SliceRandom},
+    SeedableRng,
+};
+use std::{cell::RefCell, collections::HashSet};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// 用给定的种子重置随机数生成器，使之后的生成结果可复现
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
 macro_rules! distribute {
     ($output:ty; $($freq:expr, $func:expr);+) => {{
         let mut v: Vec<(usize, fn() -> Option<$output>)> = Vec::new();
@@ -24,8 +46,9 @@ macro_rules! random_range {
                 x, y, x, y,
             ))
         } else {
-            use rand::prelude::{thread_rng, Rng};
-            thread_rng().gen_range(x, y + 1)
+            // 用闭区间的均匀分布，y为类型的最大值时也不会溢出
+            use rand::{distributions::Uniform, Rng};
+            crate::random::with_rng(|rng| rng.sample(Uniform::new_inclusive(x, y)))
         }
     }};
 }
@@ -48,6 +71,118 @@ pub fn distribute<I>(v: Vec<(usize, I)>) -> Option<I> {
     )
 }
 
+/// 按权重选择一项，权重可以是小数
+pub fn pick<I>(v: &[(f64, I)]) -> &I {
+    let dist = WeightedIndex::new(v.iter().map(|(w, _)| *w)).unwrap_or_else(|_| {
+        error_info("The weights of pick must be non-negative and cannot all be zero")
+    });
+    &v[with_rng(|rng| dist.sample(rng))].1
+}
+
+/// 不重复地选择k项，选出的项按随机顺序排列
+pub fn sample<I>(v: &[I], k: usize) -> Vec<&I> {
+    if k > v.len() {
+        error_info(&format!(
+            "Tried to sample {} distinct options from only {} options",
+            k,
+            v.len()
+        ))
+    }
+    with_rng(|rng| {
+        index::sample(rng, v.len(), k)
+            .into_iter()
+            .map(|i| &v[i])
+            .collect()
+    })
+}
+
+/// 按shape给出的分布生成[l, r]间的整数
+pub fn shaped(l: i64, r: i64, shape: &Shape) -> Option<i64> {
+    if l > r {
+        error_info(&format!(
+            "Tried to generate random integer between {} and {}, but {} > {}",
+            l, r, l, r,
+        ));
+    }
+    let len = (r as i128 - l as i128) as u64;
+    Some(match shape {
+        Shape::Extreme => {
+            // 到较近一端的距离的位数均匀分布
+            let k = log_uniform(0, len) as i128;
+            if random_range!(0, 1) == 0 {
+                (l as i128 + k) as i64
+            } else {
+                (r as i128 - k) as i64
+            }
+        }
+        Shape::Log if l >= 0 => log_uniform(l as u64, r as u64) as i64,
+        Shape::Log if r < 0 => -(log_uniform(-r as u64, (-(l as i128)) as u64) as i128) as i64,
+        Shape::Log => {
+            // 正负两侧各占一半
+            if random_range!(0, 1) == 0 {
+                log_uniform(0, r as u64) as i64
+            } else {
+                -(log_uniform(1, (-(l as i128)) as u64) as i128) as i64
+            }
+        }
+        Shape::Normal => {
+            let sum: i128 = (0..6).map(|_| random_range!(0, len) as i128).sum();
+            (l as i128 + sum / 6) as i64
+        }
+        Shape::Max(p) => {
+            let p = resolve!(p, int, IntParameter);
+            if !(0..=100).contains(&p) {
+                error_info(&format!(
+                    "The probability of max should be between 0 and 100, but {} was given",
+                    p
+                ));
+            }
+            if random_range!(1, 100) <= p {
+                r
+            } else {
+                random_range!(l, r)
+            }
+        }
+    })
+}
+
+/// 在[l, r]中生成位数均匀分布的数：先等概率地选择位数，再在这一位数的数中均匀选择
+fn log_uniform(l: u64, r: u64) -> u64 {
+    let mut buckets = Vec::new();
+    let mut lo = 0u128;
+    for d in 1..=20 {
+        let hi = 10u128.pow(d) - 1;
+        let (a, b) = (lo.max(l as u128), hi.min(r as u128));
+        if a <= b {
+            buckets.push((a as u64, b as u64));
+        }
+        lo = hi + 1;
+    }
+    let (a, b) = buckets[random_range!(0, buckets.len() - 1)];
+    random_range!(a, b)
+}
+
+/// 生成1 <= l <= r <= n的区间，short和long的长度分别不超过n/10、不少于n - n/10
+pub fn interval(n: i64, kind: IntervalKind) -> (i64, i64) {
+    if n < 1 {
+        error_info(&format!(
+            "Tried to generate an interval in an array of length {}",
+            n
+        ));
+    }
+    let len = match kind {
+        IntervalKind::Uniform => {
+            let (x, y) = (random_range!(1, n), random_range!(1, n));
+            return (x.min(y), x.max(y));
+        }
+        IntervalKind::Short => random_range!(1, (n / 10).max(1)),
+        IntervalKind::Long => random_range!(n - n / 10, n),
+        IntervalKind::Full => n,
+    };
+    let l = random_range!(1, n - len + 1);
+    (l, l + len - 1)
+}
+
 pub fn random_pair(l1: i64, r1: i64, l2: i64, r2: i64, op: Cmp) -> (i64, i64) {
     match op {
         Cmp::LessThan => {
@@ -80,6 +215,26 @@ pub fn random_pair(l1: i64, r1: i64, l2: i64, r2: i64, op: Cmp) -> (i64, i64) {
                 }
             }
         }
+        Cmp::Equal => {
+            let x = random_range!(l1.max(l2), r1.min(r2));
+            (x, x)
+        }
+        Cmp::NotEqual => {
+            if l1 == r1 && l2 == r2 && l1 == l2 {
+                use crate::details::error_info;
+                error_info(&format!(
+                    "Tried to generate two different integers, but both can only be {}",
+                    l1
+                ))
+            }
+            loop {
+                let x = random_range!(l1, r1);
+                let y = random_range!(l2, r2);
+                if x != y {
+                    return (x, y);
+                }
+            }
+        }
     }
 }
 
@@ -87,6 +242,50 @@ pub fn random_char(l: char, r: char) -> Option<char> {
     std::char::from_u32(random_range!(l as u32, r as u32))
 }
 
+/// 均匀随机的n对括号组成的合法序列，true表示左括号
+/// 把n个左括号和n+1个右括号随机排列，由循环引理，恰有一种轮换使去掉最后一个右括号后合法
+fn dyck_word(n: usize) -> Vec<bool> {
+    let mut v = vec![true; n];
+    v.extend(vec![false; n + 1]);
+    with_rng(|rng| v.shuffle(rng));
+    let (mut sum, mut min, mut pos) = (0, 0, 0);
+    for (i, open) in v.iter().enumerate() {
+        sum += if *open { 1 } else { -1 };
+        if sum < min {
+            min = sum;
+            pos = i;
+        }
+    }
+    v.rotate_left(pos + 1);
+    v.pop();
+    v
+}
+
+/// Fibonacci串和Thue-Morse串由两种字符组成
+fn two_chars(s: &str) -> (char, char) {
+    let v: Vec<char> = s.chars().collect();
+    match v[..] {
+        [a, b] => (a, b),
+        _ => error_info(&format!("Expected two characters, but \"{}\" was given", s)),
+    }
+}
+
+/// 用前缀函数求最小周期：长度减去最长的真border
+fn min_period(s: &[char]) -> usize {
+    let mut pi = vec![0; s.len()];
+    for i in 1..s.len() {
+        let mut k = pi[i - 1];
+        while k > 0 && s[i] != s[k] {
+            k = pi[k - 1];
+        }
+        if s[i] == s[k] {
+            k += 1;
+        }
+        pi[i] = k;
+    }
+    s.len() - pi.last().copied().unwrap_or(0)
+}
+
 pub fn random_string(rs: &RandomString) -> Option<String> {
     use IntParameter::*;
     let mut s = String::new();
@@ -94,7 +293,14 @@ pub fn random_string(rs: &RandomString) -> Option<String> {
         OneOf(dict, t) => {
             let dict: Vec<char> = resolve!(dict, str, StrParameter).chars().collect();
             for _ in 0..resolve!(t, size) {
-                s.push(*dict[..].choose(&mut thread_rng())?);
+                s.push(*with_rng(|rng| dict[..].choose(rng))?);
+            }
+            Some(s)
+        }
+        Weighted(weights, t) => {
+            let dist = WeightedIndex::new(weights.iter().map(|(_, w)| *w)).ok()?;
+            for _ in 0..resolve!(t, size) {
+                s.push(weights[with_rng(|rng| dist.sample(rng))].0);
             }
             Some(s)
         }
@@ -136,6 +342,100 @@ pub fn random_string(rs: &RandomString) -> Option<String> {
             }
             Some(s)
         }
+        Regex(r) => {
+            r.generate(&mut s)?;
+            Some(s)
+        }
+        Palindrome(dict, t) => {
+            let dict: Vec<char> = resolve!(dict, str, StrParameter).chars().collect();
+            let len = resolve!(t, size);
+            let mut v = Vec::new();
+            for _ in 0..len - len / 2 {
+                v.push(*with_rng(|rng| dict[..].choose(rng))?);
+            }
+            s.extend(v.iter());
+            s.extend(v[..len / 2].iter().rev());
+            Some(s)
+        }
+        Brackets(pairs, t) => {
+            let pairs: Vec<char> = resolve!(pairs, str, StrParameter).chars().collect();
+            let len = resolve!(t, size);
+            if pairs.is_empty() || pairs.len() & 1 == 1 {
+                error_info("The brackets should be given in pairs, such as \"()[]\"");
+            }
+            if len % 2 != 0 {
+                error_info(&format!(
+                    "Tried to generate a bracket sequence of odd length {}",
+                    len
+                ));
+            }
+            let mut stack = Vec::new();
+            for open in dyck_word(len / 2) {
+                if open {
+                    let k = random_range!(0, pairs.len() / 2 - 1);
+                    s.push(pairs[2 * k]);
+                    stack.push(pairs[2 * k + 1]);
+                } else {
+                    s.push(stack.pop()?);
+                }
+            }
+            Some(s)
+        }
+        Periodic(dict, p, t) => {
+            let dict: Vec<char> = resolve!(dict, str, StrParameter).chars().collect();
+            let (p, len) = (resolve!(p, size), resolve!(t, size));
+            if p == 0 || p > len {
+                error_info(&format!(
+                    "Tried to generate a string of length {} with period {}",
+                    len, p
+                ));
+            }
+            let distinct: HashSet<&char> = dict.iter().collect();
+            if p > 1 && distinct.len() < 2 {
+                error_info(&format!(
+                    "Tried to generate a string with period {} using {} distinct characters",
+                    p,
+                    distinct.len()
+                ));
+            }
+            // 重新生成循环节，直到整个串的最小周期恰好为p
+            loop {
+                let mut base = Vec::new();
+                for _ in 0..p {
+                    base.push(*with_rng(|rng| dict[..].choose(rng))?);
+                }
+                let s: Vec<char> = base.into_iter().cycle().take(len).collect();
+                if min_period(&s) == p {
+                    break Some(s.into_iter().collect());
+                }
+            }
+        }
+        Fibonacci(dict, t) => {
+            let (a, b) = two_chars(&resolve!(dict, str, StrParameter));
+            let len = resolve!(t, size);
+            let (mut prev, mut cur) = (a.to_string(), format!("{}{}", a, b));
+            while cur.chars().count() < len {
+                let next = cur.clone() + &prev;
+                prev = cur;
+                cur = next;
+            }
+            Some(cur.chars().take(len).collect())
+        }
+        AntiHash(base, modulus) => {
+            let modulus = match modulus {
+                Some(m) => Some(resolve!(m, int)),
+                None => None,
+            };
+            let (a, b) = collision(resolve!(base, int), modulus);
+            Some(a + "\n" + &b)
+        }
+        ThueMorse(dict, t) => {
+            let (a, b) = two_chars(&resolve!(dict, str, StrParameter));
+            for i in 0..resolve!(t, size) {
+                s.push(if i.count_ones() % 2 == 0 { a } else { b });
+            }
+            Some(s)
+        }
         Lower(t) => random_string(&Between('a', 'z', t.clone())),
         Upper(t) => random_string(&Between('A', 'Z', t.clone())),
         Bin(t) => random_string(&Between('0', '1', t.clone())),
@@ -144,3 +444,88 @@ pub fn random_string(rs: &RandomString) -> Option<String> {
         Graph(t) => random_string(&Between('!', '~', t.clone())),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval() {
+        for &n in [1, 2, 9, 10, 11, 100, 1000].iter() {
+            for _ in 0..200 {
+                let (l, r) = interval(n, IntervalKind::Uniform);
+                assert!(1 <= l && l <= r && r <= n);
+                let (l, r) = interval(n, IntervalKind::Short);
+                assert!(1 <= l && l <= r && r <= n && r - l < (n / 10).max(1));
+                let (l, r) = interval(n, IntervalKind::Long);
+                assert!(1 <= l && l <= r && r <= n && r - l + 1 >= n - n / 10);
+                assert_eq!(interval(n, IntervalKind::Full), (1, n));
+            }
+        }
+    }
+
+    #[test]
+    fn test_min_period() {
+        let period = |s: &str| min_period(&s.chars().collect::<Vec<char>>());
+        assert_eq!(period("aba"), 2);
+        assert_eq!(period("abab"), 2);
+        assert_eq!(period("aaaa"), 1);
+        assert_eq!(period("abcab"), 3);
+        assert_eq!(period("aab"), 3);
+    }
+
+    #[test]
+    fn test_periodic() {
+        let periodic = |dict: &str, p: i64, len: i64| {
+            Periodic(
+                StrParameter::Confirm(dict.to_string()),
+                IntParameter::Confirm(p),
+                IntParameter::Confirm(len),
+            )
+        };
+        for &(dict, p, len) in [("ab", 2, 3), ("ab", 3, 5), ("ab", 5, 5), ("01", 4, 100)].iter() {
+            for _ in 0..50 {
+                let s: Vec<char> = random_string(&periodic(dict, p, len))
+                    .unwrap()
+                    .chars()
+                    .collect();
+                assert_eq!(s.len(), len as usize);
+                assert_eq!(min_period(&s), p as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shaped() {
+        let shapes = [
+            Shape::Extreme,
+            Shape::Log,
+            Shape::Normal,
+            Shape::Max(IntParameter::Confirm(30)),
+        ];
+        let ranges = [
+            (0, 0),
+            (-5, 5),
+            (1, 1_000_000_000),
+            (-1_000_000, -1),
+            (i64::MIN, i64::MAX),
+            (i64::MIN, 0),
+            (0, i64::MAX),
+        ];
+        for shape in shapes.iter() {
+            for &(l, r) in ranges.iter() {
+                for _ in 0..500 {
+                    let x = shaped(l, r, shape).unwrap();
+                    assert!(l <= x && x <= r, "{:?} {} {} {}", shape, l, r, x);
+                }
+            }
+        }
+        // 位数均匀分布时，[1, 10^9]中的一位数约占1/9
+        let small = (0..9000)
+            .filter(|_| shaped(1, 1_000_000_000, &Shape::Log).unwrap() < 10)
+            .count();
+        assert!(800 < small && small < 1200);
+        let max = |p| Shape::Max(IntParameter::Confirm(p));
+        assert!((0..100).all(|_| shaped(0, 1000, &max(100)) == Some(1000)));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{details::error_info, random::with_rng, random_range};
+use rand::distributions::{Distribution, WeightedIndex};
+use simple_combinators::{
+    combinator::{attempt, many, many1, optional, satisfy},
+    parser::{any, char, number, string},
+    ParseError, Parser,
+};
+use Node::*;
+
+/// `*`和`+`最多重复的次数
+const REPEAT_LIMIT: usize = 8;
+
+/// 正则表达式的一个子集：字符类、连接、选择和有上限的重复
+#[derive(Clone, Debug)]
+pub enum Node {
+    Class(Vec<(char, char)>), // 字符落在其中某个闭区间内，区间有序且互不相交
+    Concat(Vec<Regex>),
+    Alt(Vec<Regex>),
+    Repeat(Box<Regex>, usize, usize),
+}
+
+/// 正则表达式的节点，以及在构造时算好的能匹配的字符串数目（按推导方式计）的自然对数
+/// 取对数避免数目过大时溢出，缓存下来避免每次生成时递归地重新计算
+#[derive(Clone, Debug)]
+pub struct Regex {
+    node: Node,
+    log_count: f64,
+}
+
+/// 解析正则表达式，语法错误时返回None
+pub fn parse(s: &str) -> Option<Regex> {
+    let mut buf = s;
+    let regex = RegexParser.parse(&mut buf).ok()?;
+    if !buf.is_empty() {
+        return None;
+    }
+    if regex.log_count == f64::NEG_INFINITY {
+        error_info(&format!("The regex \"{}\" cannot match any string", s));
+    }
+    Some(regex)
+}
+
+impl Regex {
+    fn new(node: Node) -> Regex {
+        let log_count = match &node {
+            Class(v) => (class_size(v) as f64).ln(),
+            Concat(v) => v.iter().map(|r| r.log_count).sum(),
+            Alt(v) => log_sum_exp(v.iter().map(|r| r.log_count).collect()),
+            Repeat(r, lo, hi) => log_sum_exp(repeat_weights(r.log_count, *lo, *hi)),
+        };
+        Regex { node, log_count }
+    }
+    /// 各分支、各重复次数按能匹配的字符串数目加权选择，使没有歧义的表达式均匀地生成
+    pub fn generate(&self, s: &mut String) -> Option<()> {
+        match &self.node {
+            Class(v) => {
+                let mut k = random_range!(0, class_size(v) - 1);
+                for (l, r) in v.iter() {
+                    let len = *r as u32 - *l as u32 + 1;
+                    if k < len {
+                        s.push(std::char::from_u32(*l as u32 + k)?);
+                        break;
+                    }
+                    k -= len;
+                }
+            }
+            Concat(v) => {
+                for r in v.iter() {
+                    r.generate(s)?;
+                }
+            }
+            Alt(v) => {
+                let weights: Vec<f64> = v.iter().map(|r| r.log_count).collect();
+                v[choose(&weights)?].generate(s)?;
+            }
+            Repeat(r, lo, hi) => {
+                let times = lo + choose(&repeat_weights(r.log_count, *lo, *hi))?;
+                for _ in 0..times {
+                    r.generate(s)?;
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+fn class_size(v: &[(char, char)]) -> u32 {
+    v.iter().map(|(l, r)| *r as u32 - *l as u32 + 1).sum()
+}
+
+/// 重复k次的字符串数目为c^k，取对数后为k*ln(c)
+fn repeat_weights(log_count: f64, lo: usize, hi: usize) -> Vec<f64> {
+    (lo..=hi)
+        .map(|k| if k == 0 { 0.0 } else { k as f64 * log_count })
+        .collect()
+}
+
+fn log_sum_exp(v: Vec<f64>) -> f64 {
+    let max = v.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + v.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+/// 按取了对数的权重选择一项
+fn choose(log_weights: &[f64]) -> Option<usize> {
+    let max = log_weights
+        .iter()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let dist = WeightedIndex::new(log_weights.iter().map(|x| (x - max).exp())).ok()?;
+    Some(with_rng(|rng| dist.sample(rng)))
+}
+
+/// 排序并合并相交或相邻的区间
+fn normalize(mut v: Vec<(char, char)>) -> Vec<(char, char)> {
+    v.sort();
+    let mut res: Vec<(char, char)> = Vec::new();
+    for (l, r) in v.into_iter() {
+        match res.last_mut() {
+            Some(last) if l as u32 <= last.1 as u32 + 1 => last.1 = last.1.max(r),
+            _ => res.push((l, r)),
+        }
+    }
+    res
+}
+
+/// `[^...]`取可打印的ASCII字符中不在v里的部分
+fn complement(v: Vec<(char, char)>) -> Vec<(char, char)> {
+    let mut res = Vec::new();
+    let mut next = ' ' as u32;
+    for (l, r) in normalize(v).into_iter() {
+        if l as u32 > next {
+            res.push((next, (l as u32 - 1).min('~' as u32)));
+        }
+        next = next.max(r as u32 + 1);
+    }
+    if next <= '~' as u32 {
+        res.push((next, '~' as u32));
+    }
+    res.into_iter()
+        .filter(|(l, r)| l <= r)
+        .filter_map(|(l, r)| Some((std::char::from_u32(l)?, std::char::from_u32(r)?)))
+        .collect()
+}
+
+/// 转义字符，如`\d`、`\w`、`\.`
+fn escape() -> impl Parser<ParseResult = Vec<(char, char)>> {
+    char('\\').with(any()).map(|c| match c {
+        'd' => vec![('0', '9')],
+        'w' => vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+        's' => vec![(' ', ' ')],
+        'n' => vec![('\n', '\n')],
+        't' => vec![('\t', '\t')],
+        c => vec![(c, c)],
+    })
+}
+
+/// 方括号内的一项：转义字符、字符区间或单个字符
+fn class_item() -> impl Parser<ParseResult = Vec<(char, char)>> {
+    let single = || satisfy(|c: char| c != ']' && c != '\\');
+    escape()
+        .or(attempt(
+            single()
+                .skip(char('-'))
+                .and(single())
+                .map(|(l, r)| vec![(l, r)]),
+        ))
+        .or(single().map(|c| vec![(c, c)]))
+}
+
+fn class() -> impl Parser<ParseResult = Regex> {
+    char('[')
+        .with(optional(char('^')))
+        .and(many1(class_item()))
+        .skip(char(']'))
+        .flat_map(|(negated, v): (Option<char>, Vec<Vec<(char, char)>>)| {
+            let v: Vec<(char, char)> = v.into_iter().flatten().collect();
+            if v.iter().any(|(l, r)| l > r) {
+                return None; // 区间的左端点大于右端点
+            }
+            match negated {
+                Some(_) => Some(Regex::new(Class(complement(v)))),
+                None => Some(Regex::new(Class(normalize(v)))),
+            }
+        })
+}
+
+#[derive(Copy, Clone)]
+struct GroupParser;
+impl Parser for GroupParser {
+    type ParseResult = Regex;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        char('(')
+            .skip(optional(string("?:")))
+            .with(RegexParser)
+            .skip(char(')'))
+            .parse(buf)
+    }
+}
+
+fn atom() -> impl Parser<ParseResult = Regex> {
+    GroupParser
+        .or(class())
+        .or(char('.').map(|_| Regex::new(Class(vec![(' ', '~')]))))
+        .or(escape().map(|v| Regex::new(Class(normalize(v)))))
+        .or(satisfy(|c: char| !"()[{|*+?.\\".contains(c)).map(|c| Regex::new(Class(vec![(c, c)]))))
+}
+
+/// 量词`?`、`*`、`+`、`{n}`、`{n,}`、`{n,m}`，返回重复次数的范围
+fn quantifier() -> impl Parser<ParseResult = (usize, usize)> {
+    let bounded = number()
+        .and(optional(char(',').with(optional(number()))))
+        .between(char('{'), char('}'))
+        .flat_map(|(lo, op): (usize, Option<Option<usize>>)| {
+            let hi = match op {
+                None => lo,
+                Some(None) => lo + REPEAT_LIMIT,
+                Some(Some(hi)) => hi,
+            };
+            (lo <= hi).then_some((lo, hi))
+        });
+    char('?')
+        .map(|_| (0, 1))
+        .or(char('*').map(|_| (0, REPEAT_LIMIT)))
+        .or(char('+').map(|_| (1, REPEAT_LIMIT)))
+        .or(attempt(bounded))
+}
+
+fn piece() -> impl Parser<ParseResult = Regex> {
+    atom()
+        .and(many(quantifier()))
+        .map(|(atom, v): (Regex, Vec<(usize, usize)>)| {
+            v.into_iter()
+                .fold(atom, |r, (lo, hi)| Regex::new(Repeat(Box::new(r), lo, hi)))
+        })
+}
+
+#[derive(Copy, Clone)]
+struct RegexParser;
+impl Parser for RegexParser {
+    type ParseResult = Regex;
+    fn parse<'a>(&self, buf: &mut &'a str) -> Result<Self::ParseResult, ParseError<'a>> {
+        let concat = many(attempt(piece())).map(|mut v: Vec<Regex>| {
+            if v.len() == 1 {
+                v.pop().unwrap()
+            } else {
+                Regex::new(Concat(v))
+            }
+        });
+        concat
+            .sep_by(char('|'))
+            .map(|mut v: Vec<Regex>| {
+                if v.len() == 1 {
+                    v.pop().unwrap()
+                } else {
+                    Regex::new(Alt(v))
+                }
+            })
+            .parse(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn count(s: &str) -> f64 {
+        parse(s).unwrap().log_count.exp().round()
+    }
+
+    #[test]
+    fn test_count() {
+        assert_eq!(count("[a-c]{2}"), 9.0);
+        assert_eq!(count("a|b|[0-9]"), 12.0);
+        assert_eq!(count("ab?"), 2.0);
+        assert_eq!(count("(?:x|y){0,2}"), 7.0);
+        assert_eq!(count("[^!-~]"), 1.0);
+        assert_eq!(count("\\d{3}-\\w"), 63000.0);
+        assert!(parse("a{3,2}").is_none());
+        assert!(parse("[b-a]").is_none());
+        assert!(parse("(ab").is_none());
+    }
+
+    #[test]
+    fn test_uniform() {
+        let regex = parse("[ab]{0,2}|c").unwrap();
+        let mut freq = HashMap::new();
+        for _ in 0..8000 {
+            let mut s = String::new();
+            regex.generate(&mut s).unwrap();
+            *freq.entry(s).or_insert(0) += 1;
+        }
+        // 共8种结果，每种约出现1000次
+        assert_eq!(freq.len(), 8);
+        assert!(freq.values().all(|&k| 800 < k && k < 1200));
+    }
+}
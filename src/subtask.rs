@@ -0,0 +1,131 @@
+use crate::details::{error_info, warning_info};
+use std::{fs, ops::Range, path::Path};
+
+static METADATA: &str = "subtasks.json";
+
+/// 文件标注后的子任务标记，如`@large[70, small]`
+#[derive(Clone, Debug)]
+pub struct Mark {
+    pub name: String,
+    pub score: Option<i64>,
+    pub depends: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Subtask {
+    pub name: String,
+    pub score: i64,
+    pub depends: Vec<String>,
+    pub cases: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Subtasks {
+    list: Vec<Subtask>, // 按声明顺序排列
+}
+
+impl Subtasks {
+    pub fn add(&mut self, mark: Mark, range: Range<usize>) {
+        match self.list.iter_mut().find(|s| s.name == mark.name) {
+            Some(s) => {
+                if mark.score.is_some() {
+                    warning_info(&format!(
+                        "Subtask '{}' has been declared before, the new score and dependencies are ignored",
+                        mark.name
+                    ));
+                }
+                for id in range {
+                    if !s.cases.contains(&id) {
+                        s.cases.push(id);
+                    }
+                }
+            }
+            None => self.list.push(Subtask {
+                score: mark.score.unwrap_or_else(|| {
+                    warning_info(&format!(
+                        "Subtask '{}' is used without a score, assuming 0",
+                        mark.name
+                    ));
+                    0
+                }),
+                name: mark.name,
+                depends: mark.depends,
+                cases: range.collect(),
+            }),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Subtask> {
+        self.list.iter()
+    }
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.list.iter().position(|s| s.name == name)
+    }
+    pub fn find(&self, fileid: usize) -> Option<&Subtask> {
+        self.list.iter().find(|s| s.cases.contains(&fileid))
+    }
+    /// 检查依赖是否都已声明且不成环，并提示总分不为100的情况
+    pub fn check(&self) {
+        for s in self.list.iter() {
+            for d in s.depends.iter() {
+                if self.position(d).is_none() {
+                    error_info(&format!(
+                        "Subtask '{}' depends on an undeclared subtask '{}'",
+                        s.name, d
+                    ));
+                }
+            }
+        }
+        let mut state = vec![0u8; self.list.len()]; // 0: 未访问，1: 访问中，2: 已完成
+        for i in 0..self.list.len() {
+            self.visit(i, &mut state);
+        }
+        let total: i64 = self.list.iter().map(|s| s.score).sum();
+        if !self.list.is_empty() && total != 100 {
+            warning_info(&format!(
+                "The scores of subtasks sum up to {}, not 100",
+                total
+            ));
+        }
+    }
+    fn visit(&self, i: usize, state: &mut Vec<u8>) {
+        match state[i] {
+            1 => error_info(&format!(
+                "Subtask '{}' has cyclic dependencies",
+                self.list[i].name
+            )),
+            2 => return,
+            _ => {}
+        }
+        state[i] = 1;
+        for d in self.list[i].depends.iter() {
+            self.visit(self.position(d).unwrap(), state);
+        }
+        state[i] = 2;
+    }
+    pub fn write(&self, folder: &Path) {
+        let items: Vec<String> = self
+            .list
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let depends: Vec<String> = s.depends.iter().map(|d| format!("\"{}\"", d)).collect();
+                let cases: Vec<String> = s.cases.iter().map(|c| c.to_string()).collect();
+                format!(
+                    "    {{\"id\": {}, \"name\": \"{}\", \"score\": {}, \"depends\": [{}], \"cases\": [{}]}}",
+                    i + 1,
+                    s.name,
+                    s.score,
+                    depends.join(", "),
+                    cases.join(", ")
+                )
+            })
+            .collect();
+        let json = format!("{{\n  \"subtasks\": [\n{}\n  ]\n}}\n", items.join(",\n"));
+        fs::write(folder.join(METADATA), json).unwrap_or_else(|_| {
+            error_info("Some unknown error occurred while trying to write the subtask metadata")
+        });
+    }
+}
@@ -1,11 +1,15 @@
 use crate::{
-    details::With,
-    random::{distribute, random_pair, random_string},
-    random_range, resolve,
+    details::{error_info, With},
+    geometry::{format_coord, geometry},
+    grid::{grid, maze, render},
+    number::number,
+    random::{distribute, interval, pick, random_pair, random_string, sample, shaped},
+    random_range, regex, resolve,
     token::{Parameter::*, Token::*},
+    variable::{self, with_bindings},
 };
 use num::ToPrimitive;
-use std::collections::HashMap;
+use std::{collections::HashMap, convert::TryFrom};
 
 pub type Config = HashMap<String, Vec<Parameter>>;
 #[derive(Clone, Debug)]
@@ -21,23 +25,40 @@ pub enum RandomString {
     Alnum(IntParameter),
     Graph(IntParameter),
     OneOf(StrParameter, IntParameter),
+    Weighted(Vec<(char, f64)>, IntParameter), // 每个字符按权重随机选择
     Between(char, char, IntParameter),
+    Regex(regex::Regex),
+    Palindrome(StrParameter, IntParameter),
+    Brackets(StrParameter, IntParameter), // 字符串中相邻的两个字符为一对括号
+    Periodic(StrParameter, IntParameter, IntParameter),
+    Fibonacci(StrParameter, IntParameter),
+    ThueMorse(StrParameter, IntParameter),
+    AntiHash(IntParameter, Option<IntParameter>), // 模数为None时表示对2^64自然溢出
+}
+/// 随机整数的分布
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Extreme,           // 靠近两端的数更多
+    Log,               // 位数均匀分布
+    Normal,            // 多个均匀分布的平均值，集中在中间
+    Max(IntParameter), // 以p%的概率取最大值，否则均匀分布
 }
 #[derive(Clone, Debug)]
 pub enum RandomInteger {
     Between(IntParameter, IntParameter),
     NoGreaterThan(IntParameter),
+    Shaped(IntParameter, IntParameter, Shape),
 }
 impl RandomInteger {
     pub fn left(&self) -> IntParameter {
         match self {
-            RandomInteger::Between(l, _) => l.clone(),
+            RandomInteger::Between(l, _) | RandomInteger::Shaped(l, _, _) => l.clone(),
             RandomInteger::NoGreaterThan(_) => IntParameter::Confirm(0),
         }
     }
     pub fn right(&self) -> IntParameter {
         match self {
-            RandomInteger::Between(_, r) => r.clone(),
+            RandomInteger::Between(_, r) | RandomInteger::Shaped(_, r, _) => r.clone(),
             RandomInteger::NoGreaterThan(r) => r.clone(),
         }
     }
@@ -48,7 +69,87 @@ pub enum Cmp {
     GreaterThan,
     NoLessThan,
     NoGreaterThan,
+    Equal,
+    NotEqual,
+}
+impl Cmp {
+    pub fn test(self, a: i64, b: i64) -> bool {
+        match self {
+            Cmp::LessThan => a < b,
+            Cmp::GreaterThan => a > b,
+            Cmp::NoLessThan => a >= b,
+            Cmp::NoGreaterThan => a <= b,
+            Cmp::Equal => a == b,
+            Cmp::NotEqual => a != b,
+        }
+    }
+}
+#[derive(Copy, Clone, Debug)]
+pub enum IntervalKind {
+    Uniform, // 两个端点独立均匀地选择
+    Short,
+    Long,
+    Full,
+}
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NumberKind {
+    Prime,
+    Composite,
+    Semiprime,
+    HighlyComposite, // 不超过n的数中约数最多的
+    Carmichael,
+    Pow2,
+}
+/// 重复的具名参数：重复之间的分隔符和循环变量名
+#[derive(Clone, Debug, Default)]
+pub struct RepeatOption {
+    pub sep: Option<String>,
+    pub var: Option<String>,
+}
+/// 网格的具名参数
+#[derive(Clone, Debug)]
+pub struct GridOption {
+    pub chars: Vec<char>,                // 第一个字符为空地，其余为障碍
+    pub density: Option<IntParameter>,   // 障碍所占的百分比
+    pub path: bool,                      // 保证起点到终点有一条只经过空地的路径
+    pub from: [Option<IntParameter>; 2], // 路径起点的行列（从1开始），默认为左上角
+    pub to: [Option<IntParameter>; 2],   // 路径终点的行列（从1开始），默认为右下角
+    pub sep: Option<String>,
+    pub value: Option<IntParameter>, // 指定时生成整数矩阵
+}
+#[derive(Copy, Clone, Debug)]
+pub enum MazeAlgo {
+    Dfs,
+    Prim,
+    Kruskal,
+}
+/// 迷宫的具名参数
+#[derive(Clone, Debug)]
+pub struct MazeOption {
+    pub algo: MazeAlgo,
+    pub wall: char,
+    pub path: char,
+    pub start: Option<char>, // 左上角的标记
+    pub end: Option<char>,   // 右下角的标记
+    pub extra: IntParameter, // 生成完美迷宫后额外打通的墙数
+    pub sep: Option<String>,
 }
+#[derive(Copy, Clone, Debug)]
+pub enum GeoKind {
+    Points,   // 可能重合的点
+    Distinct, // 互不重合的点
+    General,  // 没有三点共线的点
+    Convex,   // 凸多边形的顶点
+    Polygon,  // 简单多边形的顶点
+    Segments, // 端点不重合的线段
+}
+/// 几何生成器的具名参数
+#[derive(Clone, Debug, Default)]
+pub struct GeoOption {
+    pub clockwise: bool, // 多边形的顶点按顺时针给出
+    pub precision: u32,  // 坐标的小数位数，为0时生成整数坐标
+}
+#[allow(clippy::enum_variant_names)] // 运算的生成器沿用SumToken等已有的命名
 #[derive(Clone, Debug)]
 pub enum Token {
     NewLine,
@@ -57,14 +158,35 @@ pub enum Token {
     RandomInteger(RandomInteger),
     RandomString(RandomString),
     TokenGroup(Vec<Token>),
-    Repeat(IntParameter, Box<Token>),
-    Array(IntParameter, Box<Token>),
+    Repeat(IntParameter, RepeatOption, Box<Token>),
+    Array(IntParameter, RepeatOption, Box<Token>),
     Distribute(Vec<(IntParameter, Token)>),
+    Pick(Vec<(f64, Token)>),          // 按权重从若干生成器中选择一个
+    Sample(IntParameter, Vec<Token>), // 从若干生成器中选择k个不同的
+    Number(NumberKind, IntParameter, IntParameter), // 在[l, r]中生成特定种类的数
+    Interval(IntParameter, IntervalKind),
+    Query(IntParameter, Vec<(IntParameter, Token)>), // 每行按权重选择一种询问，以询问的编号开头
+    Grid(IntParameter, IntParameter, GridOption),
+    Maze(IntParameter, IntParameter, MazeOption),
+    Geometry(GeoKind, IntParameter, IntParameter, IntParameter, GeoOption),
     RandomIntegerPair(IntParameter, IntParameter, IntParameter, IntParameter, Cmp),
     SumToken(Box<Token>, Box<Token>),
     DifToken(Box<Token>, Box<Token>),
     ProdToken(Box<Token>, Box<Token>),
     QuotToken(Box<Token>, Box<Token>),
+    ModToken(Box<Token>, Box<Token>),
+    NegToken(Box<Token>),
+    MinToken(Vec<Token>),
+    MaxToken(Vec<Token>),
+    AbsToken(Box<Token>),
+    PowToken(Box<Token>, Box<Token>),
+    BitAndToken(Box<Token>, Box<Token>),
+    BitOrToken(Box<Token>, Box<Token>),
+    BitXorToken(Box<Token>, Box<Token>),
+    ShlToken(Box<Token>, Box<Token>),
+    ShrToken(Box<Token>, Box<Token>),
+    Variable(String),
+    If(Box<Token>, Cmp, Box<Token>, Box<Token>, Option<Box<Token>>), // 比较两个整数，选择执行的分支
 }
 #[derive(Clone, Debug)]
 pub enum Parameter {
@@ -73,13 +195,14 @@ pub enum Parameter {
     Enum(String),
     Str(StrParameter),
     Bool(bool),
+    Named(String, Box<Parameter>), // 形如key=value的具名参数
 }
 impl Parameter {
     pub fn int(&self) -> Option<i64> {
         use IntParameter::*;
         match self {
             Int(Confirm(i)) => Some(*i),
-            Int(Lazy(i)) => i.generate()?.int(),
+            Int(Lazy(i)) => i.value()?.int(),
             _ => None,
         }
     }
@@ -87,7 +210,7 @@ impl Parameter {
         use StrParameter::*;
         match self {
             Str(Confirm(s)) => Some(s.clone()),
-            Str(Lazy(s)) => s.generate()?.str(),
+            Str(Lazy(s)) => s.value()?.str(),
             _ => None,
         }
     }
@@ -104,7 +227,12 @@ pub enum StrParameter {
 }
 
 impl Token {
-    pub fn generate(&self) -> Option<Parameter> {
+    /// 求参数的值，参数只能是整数表达式或随机字符串，与分隔符无关
+    pub fn value(&self) -> Option<Parameter> {
+        self.generate("")
+    }
+    /// 生成结果，sep为整数后面附加的分隔符
+    pub fn generate(&self, sep: &str) -> Option<Parameter> {
         use crate::token::{IntParameter::*, RandomInteger::*};
         match self {
             NewLine => Some(Char('\n')),
@@ -118,25 +246,48 @@ impl Token {
                 0,
                 resolve!(r, int)
             )))),
+            RandomInteger(Shaped(l, r, shape)) => Some(Int(IntParameter::Confirm(shaped(
+                resolve!(l, int),
+                resolve!(r, int),
+                shape,
+            )?))),
             TokenGroup(v) => {
                 let mut s = String::new();
                 for i in v.iter() {
-                    s.push_str(&i.generate_str()?);
+                    s.push_str(&i.generate_str(sep)?);
                 }
                 Some(Str(StrParameter::Confirm(s)))
             }
-            Repeat(ip, token) => {
+            Repeat(ip, option, token) => {
                 let mut s = String::new();
                 let times = resolve!(ip, size);
-                for _ in 0..times {
-                    s.push_str(&token.generate_str()?);
+                for i in 0..times {
+                    // 循环变量$i从1开始，$n为重复次数
+                    let index = i as i64 + 1;
+                    let mut bindings = vec![("i", index), ("n", times as i64)];
+                    if let Some(var) = &option.var {
+                        bindings.push((var.as_str(), index));
+                    }
+                    let item = with_bindings(&bindings, || token.generate_str(sep))?;
+                    match &option.sep {
+                        Some(join) => {
+                            // 指定了分隔符时，去掉整数后隐式附加的分隔符
+                            if i > 0 {
+                                s.push_str(join);
+                            }
+                            s.push_str(item.strip_suffix(sep).unwrap_or(&item));
+                        }
+                        None => s.push_str(&item),
+                    }
                 }
                 Some(Str(StrParameter::Confirm(s)))
             }
-            Array(ip, v) => Some(Str(StrParameter::Confirm({
+            Array(ip, option, v) => Some(Str(StrParameter::Confirm({
                 let times = resolve!(ip, size);
                 times.to_string().with('\n')
-                    + Repeat(ip.clone(), v.clone()).generate_str()?.as_str()
+                    + Repeat(Confirm(times as i64), option.clone(), v.clone())
+                        .generate_str(sep)?
+                        .as_str()
             }))),
             Distribute(v) => {
                 let mut v2 = Vec::new();
@@ -144,7 +295,112 @@ impl Token {
                     v2.push((resolve!(ip, size), token));
                 }
                 let token = distribute(v2)?;
-                token.generate()
+                token.generate(sep)
+            }
+            Pick(v) => pick(v).generate(sep),
+            Sample(k, v) => {
+                let mut s = String::new();
+                for token in sample(v, resolve!(k, size)) {
+                    s.push_str(&token.generate_str(sep)?);
+                }
+                Some(Str(StrParameter::Confirm(s)))
+            }
+            Number(kind, l, r) => Some(Int(IntParameter::Confirm(number(
+                *kind,
+                resolve!(l, int),
+                resolve!(r, int),
+            )))),
+            Interval(n, kind) => {
+                let (l, r) = interval(resolve!(n, int), *kind);
+                let mut s = l.to_string() + sep;
+                s.push_str(&r.to_string());
+                Some(Str(StrParameter::Confirm(s + sep)))
+            }
+            Query(q, v) => {
+                let mut s = String::new();
+                let times = resolve!(q, size);
+                for i in 0..times {
+                    let mut v2 = Vec::new();
+                    for (k, (ip, token)) in v.iter().enumerate() {
+                        v2.push((resolve!(ip, size), (k + 1, token)));
+                    }
+                    let (kind, token) = distribute(v2)?;
+                    let bindings = [("i", i as i64 + 1), ("n", times as i64)];
+                    let item = with_bindings(&bindings, || token.generate_str(sep))?;
+                    // 编号和结果用分隔符连接，去掉行末整数后附加的分隔符
+                    s.push_str(&(kind.to_string() + sep));
+                    s.push_str(item.strip_suffix(sep).unwrap_or(&item));
+                    s.push('\n');
+                }
+                Some(Str(StrParameter::Confirm(s)))
+            }
+            Grid(n, m, option) => {
+                let (n, m) = (resolve!(n, size), resolve!(m, size));
+                let s = match &option.value {
+                    Some(value) => {
+                        let mut rows = Vec::new();
+                        for _ in 0..n {
+                            let mut row = Vec::new();
+                            for _ in 0..m {
+                                row.push(resolve!(value, int).to_string());
+                            }
+                            rows.push(row.join(option.sep.as_deref().unwrap_or(sep)));
+                        }
+                        rows.join("\n")
+                    }
+                    None => {
+                        let density = match &option.density {
+                            Some(d) => Some(resolve!(d, int)),
+                            None => None,
+                        };
+                        let mut ends = [(1, 1), (n, m)];
+                        for (end, pos) in ends.iter_mut().zip([&option.from, &option.to].iter()) {
+                            if let Some(x) = &pos[0] {
+                                end.0 = resolve!(x, size);
+                            }
+                            if let Some(y) = &pos[1] {
+                                end.1 = resolve!(y, size);
+                            }
+                        }
+                        let path = if option.path { Some(ends) } else { None };
+                        let g = grid(n, m, &option.chars, density, path);
+                        render(&g, option.sep.as_deref().unwrap_or(""))
+                    }
+                };
+                Some(Str(StrParameter::Confirm(s)))
+            }
+            Maze(n, m, option) => {
+                let (n, m) = (resolve!(n, size), resolve!(m, size));
+                let g = maze(n, m, option, resolve!(&option.extra, size));
+                Some(Str(StrParameter::Confirm(render(
+                    &g,
+                    option.sep.as_deref().unwrap_or(""),
+                ))))
+            }
+            Geometry(kind, n, l, r, option) => {
+                // 小数坐标先放大为整数生成，保证共线、凸性等性质在输出后依然成立
+                let scale = 10i64.checked_pow(option.precision);
+                let (l, r) = (resolve!(l, int), resolve!(r, int));
+                let (sl, sr) = match scale.map(|s| (l.checked_mul(s), r.checked_mul(s))) {
+                    Some((Some(sl), Some(sr))) => (sl, sr),
+                    _ => error_info(&format!(
+                        "Coordinates between {} and {} with {} decimal places are out of range",
+                        l, r, option.precision
+                    )),
+                };
+                let rows = geometry(*kind, resolve!(n, size), sl, sr, option.clockwise);
+                let rows: Vec<String> = rows
+                    .into_iter()
+                    .map(|row| {
+                        let mut v = Vec::new();
+                        for (x, y) in row.into_iter() {
+                            v.push(format_coord(x, option.precision));
+                            v.push(format_coord(y, option.precision));
+                        }
+                        v.join(sep)
+                    })
+                    .collect();
+                Some(Str(StrParameter::Confirm(rows.join("\n"))))
             }
             RandomIntegerPair(l1, r1, l2, r2, op) => {
                 let (a, b) = random_pair(
@@ -154,51 +410,177 @@ impl Token {
                     resolve!(r2, int),
                     *op,
                 );
-                let mut s = a.to_string().with(' ');
+                let mut s = a.to_string() + sep;
                 s.push_str(&b.to_string());
-                Some(Str(StrParameter::Confirm(s.with(' '))))
+                Some(Str(StrParameter::Confirm(s + sep)))
             }
             RandomString(rs) => Some(Str(StrParameter::Confirm(random_string(&rs)?))),
-            SumToken(t1, t2) => match (t1.generate()?, t2.generate()?) {
-                (Int(i1), Int(i2)) => Some(Int(IntParameter::Confirm(
-                    resolve!(i1, int) + resolve!(i2, int),
-                ))),
-                _ => None,
-            },
-            DifToken(t1, t2) => match (t1.generate()?, t2.generate()?) {
-                (Int(i1), Int(i2)) => Some(Int(IntParameter::Confirm(
-                    resolve!(i1, int) - resolve!(i2, int),
-                ))),
-                _ => None,
-            },
-            ProdToken(t1, t2) => match (t1.generate()?, t2.generate()?) {
-                (Int(i1), Int(i2)) => Some(Int(IntParameter::Confirm(
-                    resolve!(i1, int) * resolve!(i2, int),
-                ))),
-                _ => None,
-            },
-            QuotToken(t1, t2) => match (t1.generate()?, t2.generate()?) {
-                (Int(i1), Int(i2)) => Some(Int(IntParameter::Confirm(
-                    resolve!(i1, int) / resolve!(i2, int),
-                ))),
-                _ => None,
-            },
+            SumToken(t1, t2) => calculate(t1, t2, "+", i64::checked_add),
+            DifToken(t1, t2) => calculate(t1, t2, "-", i64::checked_sub),
+            ProdToken(t1, t2) => calculate(t1, t2, "*", i64::checked_mul),
+            QuotToken(t1, t2) => calculate(t1, t2, "/", i64::checked_div),
+            ModToken(t1, t2) => calculate(t1, t2, "%", i64::checked_rem),
+            PowToken(t1, t2) => {
+                calculate(t1, t2, "pow", |a, b| a.checked_pow(u32::try_from(b).ok()?))
+            }
+            BitAndToken(t1, t2) => calculate(t1, t2, "&", |a, b| Some(a & b)),
+            BitOrToken(t1, t2) => calculate(t1, t2, "|", |a, b| Some(a | b)),
+            BitXorToken(t1, t2) => calculate(t1, t2, "^", |a, b| Some(a ^ b)),
+            ShlToken(t1, t2) => calculate(t1, t2, "<<", |a, b| {
+                // 移出的位不为0时也算作溢出
+                a.checked_shl(u32::try_from(b).ok()?)
+                    .filter(|r| r >> b == a)
+            }),
+            ShrToken(t1, t2) => {
+                calculate(t1, t2, ">>", |a, b| a.checked_shr(u32::try_from(b).ok()?))
+            }
+            NegToken(t) => {
+                let a = t.value()?.int()?;
+                Some(Int(IntParameter::Confirm(a.checked_neg().unwrap_or_else(
+                    || error_info(&format!("Integer overflow while calculating -({})", a)),
+                ))))
+            }
+            AbsToken(t) => {
+                let a = t.value()?.int()?;
+                Some(Int(IntParameter::Confirm(a.checked_abs().unwrap_or_else(
+                    || error_info(&format!("Integer overflow while calculating abs({})", a)),
+                ))))
+            }
+            MinToken(v) => {
+                let mut res = Vec::new();
+                for t in v.iter() {
+                    res.push(t.value()?.int()?);
+                }
+                Some(Int(IntParameter::Confirm(res.into_iter().min()?)))
+            }
+            MaxToken(v) => {
+                let mut res = Vec::new();
+                for t in v.iter() {
+                    res.push(t.value()?.int()?);
+                }
+                Some(Int(IntParameter::Confirm(res.into_iter().max()?)))
+            }
+            Variable(name) => Some(Int(IntParameter::Confirm(
+                variable::get(name).unwrap_or_else(|err| error_info(&err)),
+            ))),
+            If(l, op, r, then, otherwise) => {
+                let l = l.value()?.int()?;
+                let r = r.value()?.int()?;
+                match (op.test(l, r), otherwise) {
+                    (true, _) => then.generate(sep),
+                    (false, Some(otherwise)) => otherwise.generate(sep),
+                    (false, None) => Some(Str(StrParameter::Confirm(String::new()))),
+                }
+            }
         }
     }
-    pub fn generate_str(&self) -> Option<String> {
-        match self.generate()? {
-            Parameter::Int(IntParameter::Confirm(i)) => Some(i.to_string().with(' ')),
+    pub fn generate_str(&self, sep: &str) -> Option<String> {
+        match self.generate(sep)? {
+            Parameter::Int(IntParameter::Confirm(i)) => Some(i.to_string() + sep),
             Parameter::Char(c) => Some(c.to_string()),
             Parameter::Str(StrParameter::Confirm(s)) => Some(s),
             _ => None,
         }
     }
+    pub fn is_empty_group(&self) -> bool {
+        match self {
+            TokenGroup(v) => v.iter().all(|t| t.is_empty_group()),
+            _ => false,
+        }
+    }
     pub fn is_int_token(&self) -> bool {
         match self {
-            ConstantInteger(_) | RandomInteger(_) => true,
+            ConstantInteger(_) | RandomInteger(_) | Number(..) | Variable(_) => true,
             SumToken(t1, t2) if t1.is_int_token() && t2.is_int_token() => true,
             DifToken(t1, t2) if t1.is_int_token() && t2.is_int_token() => true,
+            ProdToken(t1, t2) if t1.is_int_token() && t2.is_int_token() => true,
+            QuotToken(t1, t2) | ModToken(t1, t2) | PowToken(t1, t2) => {
+                t1.is_int_token() && t2.is_int_token()
+            }
+            BitAndToken(t1, t2) | BitOrToken(t1, t2) | BitXorToken(t1, t2) => {
+                t1.is_int_token() && t2.is_int_token()
+            }
+            ShlToken(t1, t2) | ShrToken(t1, t2) => t1.is_int_token() && t2.is_int_token(),
+            NegToken(t) | AbsToken(t) => t.is_int_token(),
+            MinToken(v) | MaxToken(v) => v.iter().all(|t| t.is_int_token()),
+            Pick(v) => v.iter().all(|(_, t)| t.is_int_token()),
             _ => false,
         }
     }
 }
+
+/// 计算两个整数的运算结果，溢出、除以零等情况报错而不是panic
+fn calculate(
+    t1: &Token,
+    t2: &Token,
+    op: &str,
+    f: fn(i64, i64) -> Option<i64>,
+) -> Option<Parameter> {
+    let (a, b) = (t1.value()?.int()?, t2.value()?.int()?);
+    let expr = if op == "pow" {
+        format!("pow({}, {})", a, b)
+    } else {
+        format!("{} {} {}", a, op, b)
+    };
+    let res = f(a, b).unwrap_or_else(|| match op {
+        "/" | "%" if b == 0 => error_info(&format!("Division by zero while calculating {}", expr)),
+        "<<" | ">>" | "pow" if b < 0 => {
+            error_info(&format!("Negative operand while calculating {}", expr))
+        }
+        _ => error_info(&format!("Integer overflow while calculating {}", expr)),
+    });
+    Some(Int(IntParameter::Confirm(res)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_lines() {
+        let query = Query(
+            IntParameter::Confirm(3),
+            vec![(
+                IntParameter::Confirm(1),
+                TokenGroup(vec![ConstantInteger(4), ConstantInteger(2)]),
+            )],
+        );
+        assert_eq!(query.generate_str(" ").unwrap(), "1 4 2\n".repeat(3));
+        assert_eq!(query.generate_str(",").unwrap(), "1,4,2\n".repeat(3));
+    }
+
+    #[test]
+    fn test_grid_value_sep() {
+        let mut option = GridOption {
+            chars: vec!['.', '#'],
+            density: None,
+            path: false,
+            from: [None, None],
+            to: [None, None],
+            sep: None,
+            value: Some(IntParameter::Confirm(7)),
+        };
+        let grid = Grid(
+            IntParameter::Confirm(2),
+            IntParameter::Confirm(3),
+            option.clone(),
+        );
+        assert_eq!(grid.generate_str(",").unwrap(), "7,7,7\n7,7,7");
+        option.sep = Some("-".to_string());
+        let grid = Grid(IntParameter::Confirm(2), IntParameter::Confirm(3), option);
+        assert_eq!(grid.generate_str(",").unwrap(), "7-7-7\n7-7-7");
+    }
+
+    #[test]
+    fn test_geometry_sep() {
+        let points = Geometry(
+            GeoKind::Points,
+            IntParameter::Confirm(2),
+            IntParameter::Confirm(1),
+            IntParameter::Confirm(1),
+            GeoOption::default(),
+        );
+        assert_eq!(points.generate_str(",").unwrap(), "1,1\n1,1");
+        assert_eq!(points.generate_str(" ").unwrap(), "1 1\n1 1");
+    }
+}
@@ -0,0 +1,48 @@
+use std::cell::RefCell;
+
+thread_local! {
+    static VARIABLES: RefCell<Vec<(String, i64)>> = RefCell::default(); // 后绑定的变量覆盖先绑定的同名变量
+}
+
+/// 取出变量当前绑定的值，未绑定时（如在重复外使用`$i`）返回带有变量名的错误信息
+pub fn get(name: &str) -> Result<i64, String> {
+    VARIABLES.with(|v| {
+        v.borrow()
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, val)| *val)
+            .ok_or_else(|| format!("undefined variable `${}`", name))
+    })
+}
+
+/// 在绑定了给定变量的环境中执行f，结束后解除绑定
+pub fn with_bindings<T>(bindings: &[(&str, i64)], f: impl FnOnce() -> T) -> T {
+    VARIABLES.with(|v| {
+        v.borrow_mut()
+            .extend(bindings.iter().map(|(k, val)| (k.to_string(), *val)))
+    });
+    let res = f();
+    VARIABLES.with(|v| {
+        let mut v = v.borrow_mut();
+        let len = v.len() - bindings.len();
+        v.truncate(len);
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        assert_eq!(get("i"), Err("undefined variable `$i`".to_string()));
+        with_bindings(&[("i", 1), ("n", 3)], || {
+            with_bindings(&[("i", 2)], || assert_eq!(get("i"), Ok(2)));
+            assert_eq!(get("i"), Ok(1));
+            assert_eq!(get("n"), Ok(3));
+        });
+        assert!(get("n").is_err());
+    }
+}